@@ -0,0 +1,381 @@
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{ColorDepth, Sink};
+
+/// What an `AsyncSink` does with a line when its queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Waits for the writer thread to make room, so no line is lost.
+    #[default]
+    Block,
+    /// Drops the line being logged.
+    DropNewest,
+    /// Drops the oldest queued line to make room.
+    DropOldest,
+}
+
+struct Queue {
+    lines: VecDeque<String>,
+    // The writer thread is writing a batch it took off the queue
+    writing: bool,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    // Signalled when lines are queued or the sink closes
+    queued: Condvar,
+    // Signalled when the writer takes lines off the queue or finishes a batch
+    drained: Condvar,
+    dropped: AtomicU64,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub struct AsyncSinkBuilder {
+    sink: Arc<dyn Sink>,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+impl AsyncSinkBuilder {
+    /// How many lines can wait for the writer thread, 1024 by default.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Starts the writer thread. Keep the guard alive until the end of `main`:
+    /// dropping it writes out every queued line and stops the thread.
+    pub fn build(self) -> (AsyncSink, FlushGuard) {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                // Grows up to `capacity` as needed, which can be `usize::MAX`
+                lines: VecDeque::new(),
+                writing: false,
+                closed: false,
+            }),
+            queued: Condvar::new(),
+            drained: Condvar::new(),
+            dropped: AtomicU64::new(0),
+        });
+
+        let worker = {
+            let shared = shared.clone();
+            let sink = self.sink.clone();
+            thread::Builder::new()
+                .name("dbug-writer".into())
+                .spawn(move || drain(&shared, sink.as_ref()))
+                .ok()
+        };
+        // Without a writer thread, lines are written on the calling thread
+        if worker.is_none() {
+            shared.lock().closed = true;
+        }
+
+        let sink = AsyncSink {
+            sink: self.sink,
+            shared: shared.clone(),
+            capacity: self.capacity,
+            overflow: self.overflow,
+        };
+
+        (sink, FlushGuard { shared, worker })
+    }
+}
+
+fn drain(shared: &Shared, sink: &dyn Sink) {
+    let mut queue = shared.lock();
+    loop {
+        while queue.lines.is_empty() && !queue.closed {
+            queue = shared.queued.wait(queue).unwrap_or_else(|e| e.into_inner());
+        }
+        if queue.lines.is_empty() {
+            return;
+        }
+
+        let lines = mem::take(&mut queue.lines);
+        queue.writing = true;
+        drop(queue);
+        shared.drained.notify_all();
+
+        for line in &lines {
+            sink.write_line(line);
+        }
+
+        queue = shared.lock();
+        queue.writing = false;
+        shared.drained.notify_all();
+    }
+}
+
+/// Hands lines to a writer thread through a bounded queue, so logging costs
+/// the calling thread formatting and a queue push instead of blocking I/O.
+pub struct AsyncSink {
+    sink: Arc<dyn Sink>,
+    shared: Arc<Shared>,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+impl AsyncSink {
+    pub fn builder(sink: impl Sink + 'static) -> AsyncSinkBuilder {
+        AsyncSinkBuilder {
+            sink: Arc::new(sink),
+            capacity: 1024,
+            overflow: Overflow::Block,
+        }
+    }
+
+    /// How many lines were dropped because the queue was full. This counter is
+    /// the only place drops are reported, nothing is written to the sink.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    fn drop_line(&self) {
+        self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Sink for AsyncSink {
+    fn write_line(&self, line: &str) {
+        let mut queue = self.shared.lock();
+        if queue.closed {
+            drop(queue);
+            return self.sink.write_line(line);
+        }
+
+        if queue.lines.len() >= self.capacity {
+            match self.overflow {
+                Overflow::Block => {
+                    while queue.lines.len() >= self.capacity && !queue.closed {
+                        queue = self
+                            .shared
+                            .drained
+                            .wait(queue)
+                            .unwrap_or_else(|e| e.into_inner());
+                    }
+                    if queue.closed {
+                        drop(queue);
+                        return self.sink.write_line(line);
+                    }
+                }
+                Overflow::DropNewest => return self.drop_line(),
+                Overflow::DropOldest => {
+                    queue.lines.pop_front();
+                    self.drop_line();
+                }
+            }
+        }
+
+        queue.lines.push_back(line.to_string());
+        drop(queue);
+        self.shared.queued.notify_one();
+    }
+
+    /// Waits until the writer thread has written every queued line.
+    fn flush(&self) {
+        let mut queue = self.shared.lock();
+        while (!queue.lines.is_empty() || queue.writing) && !queue.closed {
+            queue = self
+                .shared
+                .drained
+                .wait(queue)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        drop(queue);
+
+        self.sink.flush();
+    }
+
+    fn colors(&self) -> bool {
+        self.sink.colors()
+    }
+
+    fn color_depth(&self) -> ColorDepth {
+        self.sink.color_depth()
+    }
+
+    fn single_line(&self) -> bool {
+        self.sink.single_line()
+    }
+}
+
+/// Writes out every queued line and stops the writer thread when dropped.
+/// Lines logged afterwards are written on the calling thread.
+pub struct FlushGuard {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        // Lines logged from here on are written directly, so they have to
+        // wait for the queued ones to be written first
+        let mut queue = self.shared.lock();
+        while self.worker.is_some() && (!queue.lines.is_empty() || queue.writing) {
+            queue = self
+                .shared
+                .drained
+                .wait(queue)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        queue.closed = true;
+        drop(queue);
+        self.shared.queued.notify_all();
+        self.shared.drained.notify_all();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemorySink;
+    use std::sync::mpsc::{self, SyncSender};
+
+    // Holds the writer thread inside `write_line` until the test lets go of `gate`
+    struct Gated {
+        entered: SyncSender<()>,
+        gate: Arc<Mutex<()>>,
+        lines: MemorySink,
+    }
+
+    impl Sink for Gated {
+        fn write_line(&self, line: &str) {
+            let _ = self.entered.try_send(());
+            let _gate = self.gate.lock();
+            self.lines.write_line(line);
+        }
+    }
+
+    fn gated(overflow: Overflow) -> (AsyncSink, FlushGuard, MemorySink) {
+        let (entered, writing) = mpsc::sync_channel(1);
+        let gate = Arc::new(Mutex::new(()));
+        let lines = MemorySink::new();
+        let (sink, guard) = AsyncSink::builder(Gated {
+            entered,
+            gate: gate.clone(),
+            lines: lines.clone(),
+        })
+        .capacity(2)
+        .overflow(overflow)
+        .build();
+
+        let held = gate.lock().unwrap();
+        sink.write_line("first");
+        writing.recv().unwrap();
+        for line in ["a", "b", "c", "d"] {
+            sink.write_line(line);
+        }
+        drop(held);
+
+        (sink, guard, lines)
+    }
+
+    #[test]
+    fn writes_every_line_in_order_before_the_guard_drops() {
+        let lines = MemorySink::new();
+        let (sink, guard) = AsyncSink::builder(lines.clone()).build();
+
+        for i in 0..100 {
+            sink.write_line(&i.to_string());
+        }
+        sink.flush();
+        assert_eq!(lines.lines().len(), 100);
+
+        sink.write_line("last");
+        drop(guard);
+        assert_eq!(lines.lines().last().map(String::as_str), Some("last"));
+
+        sink.write_line("after the guard");
+        assert_eq!(lines.lines().len(), 102);
+    }
+
+    #[test]
+    fn full_queues_drop_the_newest_or_oldest_lines() {
+        let (sink, guard, lines) = gated(Overflow::DropNewest);
+        drop(guard);
+        assert_eq!(sink.dropped(), 2);
+        assert_eq!(lines.lines(), ["first", "a", "b"]);
+
+        let (sink, guard, lines) = gated(Overflow::DropOldest);
+        drop(guard);
+        assert_eq!(sink.dropped(), 2);
+        assert_eq!(lines.lines(), ["first", "c", "d"]);
+    }
+
+    #[test]
+    fn lines_logged_while_the_guard_drops_come_after_queued_ones() {
+        let (entered, writing) = mpsc::sync_channel(1);
+        let gate = Arc::new(Mutex::new(()));
+        let lines = MemorySink::new();
+        let (sink, guard) = AsyncSink::builder(Gated {
+            entered,
+            gate: gate.clone(),
+            lines: lines.clone(),
+        })
+        .build();
+
+        let held = gate.lock().unwrap();
+        sink.write_line("first");
+        writing.recv().unwrap();
+        sink.write_line("queued");
+
+        let dropping = thread::spawn(move || drop(guard));
+        thread::sleep(std::time::Duration::from_millis(20));
+        sink.write_line("late");
+        drop(held);
+        dropping.join().unwrap();
+
+        assert_eq!(lines.lines(), ["first", "queued", "late"]);
+    }
+
+    #[test]
+    fn unbounded_queues_start_empty() {
+        let lines = MemorySink::new();
+        let (sink, guard) = AsyncSink::builder(lines.clone())
+            .capacity(usize::MAX)
+            .build();
+
+        sink.write_line("one");
+        drop(guard);
+
+        assert_eq!(lines.lines(), ["one"]);
+    }
+
+    #[test]
+    fn full_queues_block_until_there_is_room() {
+        let lines = MemorySink::new();
+        let (sink, guard) = AsyncSink::builder(lines.clone()).capacity(1).build();
+
+        for i in 0..50 {
+            sink.write_line(&i.to_string());
+        }
+        drop(guard);
+
+        assert_eq!(sink.dropped(), 0);
+        assert_eq!(lines.lines().len(), 50);
+    }
+}
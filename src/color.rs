@@ -0,0 +1,649 @@
+use std::{
+    env,
+    sync::{Mutex, OnceLock, RwLock},
+};
+
+// debug-js's palette for terminals with 256 colors, as xterm color codes
+const COLORS: [u8; 76] = [
+    20, 21, 26, 27, 32, 33, 38, 39, 40, 41, 42, 43, 44, 45, 56, 57, 62, 63, 68, 69, 74, 75, 76, 77,
+    78, 79, 80, 81, 92, 93, 98, 99, 112, 113, 128, 129, 134, 135, 148, 149, 160, 161, 162, 163,
+    164, 165, 166, 167, 168, 169, 170, 171, 172, 173, 178, 179, 184, 185, 196, 197, 198, 199, 200,
+    201, 202, 203, 204, 205, 206, 207, 208, 209, 214, 215, 220, 221,
+];
+
+// Channel values of xterm's 6x6x6 color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// xterm's defaults for the 16 basic colors, with their SGR foreground codes
+const BASIC_COLORS: [(u8, u8, u8, u8); 16] = [
+    (0, 0, 0, 30),
+    (205, 0, 0, 31),
+    (0, 205, 0, 32),
+    (205, 205, 0, 33),
+    (0, 0, 238, 34),
+    (205, 0, 205, 35),
+    (0, 205, 205, 36),
+    (229, 229, 229, 37),
+    (127, 127, 127, 90),
+    (255, 0, 0, 91),
+    (0, 255, 0, 92),
+    (255, 255, 0, 93),
+    (92, 92, 255, 94),
+    (255, 0, 255, 95),
+    (0, 255, 255, 96),
+    (255, 255, 255, 97),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Rgb {
+        Rgb { r, g, b }
+    }
+
+    /// Parses `#rrggbb` or `rrggbb`.
+    pub fn from_hex(hex: &str) -> Option<Rgb> {
+        let hex = hex.trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+
+        let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+        let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+        let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+
+        Some(Rgb { r, g, b })
+    }
+
+    // Only for codes in the 16-231 color cube, which is all the palette uses
+    fn from_xterm(code: u8) -> Rgb {
+        let cube = code.saturating_sub(16);
+
+        Rgb {
+            r: CUBE_LEVELS[(cube / 36 % 6) as usize],
+            g: CUBE_LEVELS[(cube / 6 % 6) as usize],
+            b: CUBE_LEVELS[(cube % 6) as usize],
+        }
+    }
+
+    fn ansi256(&self) -> u8 {
+        rgb_to_ansi256(self.r, self.g, self.b)
+    }
+
+    // Weighted for how much more sensitive eyes are to green than red or blue
+    fn distance(&self, r: u8, g: u8, b: u8) -> u32 {
+        let dr = self.r.abs_diff(r) as u32;
+        let dg = self.g.abs_diff(g) as u32;
+        let db = self.b.abs_diff(b) as u32;
+
+        2 * dr * dr + 4 * dg * dg + 3 * db * db
+    }
+
+    fn basic(&self) -> u8 {
+        BASIC_COLORS
+            .iter()
+            .min_by_key(|(r, g, b, _)| self.distance(*r, *g, *b))
+            .map_or(37, |(_, _, _, code)| *code)
+    }
+
+    // WCAG relative luminance
+    fn luminance(&self) -> f64 {
+        let channel = |value: u8| {
+            let value = value as f64 / 255.0;
+            if value <= 0.03928 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        0.2126 * channel(self.r) + 0.7152 * channel(self.g) + 0.0722 * channel(self.b)
+    }
+
+    // WCAG contrast ratio, from 1 (none) to 21 (black on white)
+    fn contrast(&self, other: &Rgb) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+}
+
+/// The terminal background namespace colors have to stay readable on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+impl Theme {
+    fn background(&self) -> Rgb {
+        match self {
+            Theme::Dark => Rgb::new(0, 0, 0),
+            Theme::Light => Rgb::new(255, 255, 255),
+        }
+    }
+}
+
+/// How namespaces get their colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorAssignment {
+    /// Hashed from the namespace, the same in every process and in Node.
+    #[default]
+    Hashed,
+    /// Picked when a logger is created to be as far as possible from the
+    /// colors of the other live loggers. Depends on creation order.
+    Distinct,
+}
+
+// WCAG's minimum for large or bold text, which namespaces always are
+const MIN_CONTRAST: f64 = 3.0;
+
+struct ColorConfig {
+    palette: Vec<Rgb>,
+    theme: Option<Theme>,
+    // The palette with the colors that are too hard to read on the theme removed
+    readable: Vec<Rgb>,
+    assignment: ColorAssignment,
+}
+
+impl ColorConfig {
+    fn new(palette: Vec<Rgb>, theme: Option<Theme>) -> Self {
+        let palette = if palette.is_empty() {
+            COLORS.iter().map(|code| Rgb::from_xterm(*code)).collect()
+        } else {
+            palette
+        };
+
+        let readable: Vec<Rgb> = match theme {
+            Some(theme) => palette
+                .iter()
+                .filter(|color| color.contrast(&theme.background()) >= MIN_CONTRAST)
+                .copied()
+                .collect(),
+            None => palette.clone(),
+        };
+
+        ColorConfig {
+            readable: if readable.is_empty() {
+                palette.clone()
+            } else {
+                readable
+            },
+            palette,
+            theme,
+            assignment: ColorAssignment::Hashed,
+        }
+    }
+
+    // DEBUG_PALETTE="#rrggbb,#rrggbb,...", DEBUG_THEME=dark|light and
+    // DEBUG_COLOR_ASSIGN=hashed|distinct
+    fn from_env() -> Self {
+        let palette = env::var("DEBUG_PALETTE")
+            .unwrap_or_default()
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter_map(Rgb::from_hex)
+            .collect();
+
+        let theme = match env::var("DEBUG_THEME").as_deref() {
+            Ok("dark") => Some(Theme::Dark),
+            Ok("light") => Some(Theme::Light),
+            _ => None,
+        };
+
+        let mut config = ColorConfig::new(palette, theme);
+        if env::var("DEBUG_COLOR_ASSIGN").as_deref() == Ok("distinct") {
+            config.assignment = ColorAssignment::Distinct;
+        }
+
+        config
+    }
+}
+
+// None until first used, then read from the environment
+static COLOR_CONFIG: RwLock<Option<ColorConfig>> = RwLock::new(None);
+
+fn with_color_config<R>(f: impl FnOnce(&mut ColorConfig) -> R) -> R {
+    let mut config = COLOR_CONFIG.write().unwrap_or_else(|e| e.into_inner());
+
+    f(config.get_or_insert_with(ColorConfig::from_env))
+}
+
+/// Replaces the palette namespace colors are picked from. Loggers created
+/// afterwards use it, an empty palette goes back to the default one.
+pub fn set_palette(palette: impl IntoIterator<Item = Rgb>) {
+    with_color_config(|config| {
+        let assignment = config.assignment;
+        *config = ColorConfig::new(palette.into_iter().collect(), config.theme);
+        config.assignment = assignment;
+    });
+}
+
+/// Leaves out palette colors that don't have enough contrast with the theme's
+/// background. `None` uses every palette color.
+pub fn set_theme(theme: Option<Theme>) {
+    with_color_config(|config| {
+        let assignment = config.assignment;
+        *config = ColorConfig::new(std::mem::take(&mut config.palette), theme);
+        config.assignment = assignment;
+    });
+}
+
+/// Switches between hashed and distinct colors for loggers created afterwards.
+pub fn set_color_assignment(assignment: ColorAssignment) {
+    with_color_config(|config| config.assignment = assignment);
+}
+
+// DEBUG_COLOR_<namespace>=#rrggbb, also accepted with every character that
+// can't be in a shell variable name replaced by `_`, e.g. DEBUG_COLOR_db_read
+fn override_from(var: impl Fn(&str) -> Option<String>, namespace: &str) -> Option<Rgb> {
+    let sanitized: String = namespace
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    var(&format!("DEBUG_COLOR_{}", namespace))
+        .or_else(|| var(&format!("DEBUG_COLOR_{}", sanitized)))
+        .and_then(|hex| Rgb::from_hex(hex.trim()))
+}
+
+// debug-js's `selectColor`: a 32-bit Java-style string hash over UTF-16 code units,
+// so a namespace gets the same color here as in a Node process
+fn select_color(namespace: &str, colors: usize) -> usize {
+    let mut hash: i32 = 0;
+    for unit in namespace.encode_utf16() {
+        hash = (hash << 5).wrapping_sub(hash).wrapping_add(unit as i32);
+    }
+
+    ((hash as i64).unsigned_abs() % colors as u64) as usize
+}
+
+pub(crate) fn color_for_string(input: &str) -> Rgb {
+    if let Some(color) = override_from(|name| env::var(name).ok(), input) {
+        return color;
+    }
+
+    with_color_config(|config| config.readable[select_color(input, config.readable.len())])
+}
+
+// The palette color furthest from every live color, trying the namespace's
+// hashed color first so ties, and the first logger, keep it
+fn most_distinct(palette: &[Rgb], start: usize, live: &[Rgb]) -> Rgb {
+    (0..palette.len())
+        .map(|i| palette[(start + i) % palette.len()])
+        .rev()
+        .max_by_key(|color| {
+            live.iter()
+                .map(|other| color.distance(other.r, other.g, other.b))
+                .min()
+                .unwrap_or(u32::MAX)
+        })
+        .unwrap_or(palette[start])
+}
+
+struct LiveColor {
+    namespace: String,
+    color: Rgb,
+    // Chosen by the builder or `DEBUG_COLOR_<ns>` rather than the allocator
+    fixed: bool,
+    loggers: usize,
+}
+
+// Colors in use by live loggers, one entry per namespace and color
+#[derive(Default)]
+struct LiveColors {
+    namespaces: Vec<LiveColor>,
+}
+
+impl LiveColors {
+    fn assign(&mut self, namespace: &str, palette: &[Rgb], fixed: Option<Rgb>) -> Rgb {
+        // Loggers without a fixed color share their namespace's allocated one
+        let shared = self.namespaces.iter().find(|live| {
+            live.namespace == namespace
+                && match fixed {
+                    Some(color) => live.color == color,
+                    None => !live.fixed,
+                }
+        });
+        let color = match (shared, fixed) {
+            (Some(live), _) => live.color,
+            (None, Some(color)) => color,
+            (None, None) => {
+                let live: Vec<Rgb> = self.namespaces.iter().map(|live| live.color).collect();
+                most_distinct(palette, select_color(namespace, palette.len()), &live)
+            }
+        };
+
+        match self
+            .namespaces
+            .iter_mut()
+            .find(|live| live.namespace == namespace && live.color == color)
+        {
+            Some(live) => live.loggers += 1,
+            None => self.namespaces.push(LiveColor {
+                namespace: namespace.to_string(),
+                color,
+                fixed: fixed.is_some(),
+                loggers: 1,
+            }),
+        }
+
+        color
+    }
+
+    fn release(&mut self, namespace: &str, color: Rgb) {
+        if let Some(i) = self
+            .namespaces
+            .iter()
+            .position(|live| live.namespace == namespace && live.color == color)
+        {
+            self.namespaces[i].loggers -= 1;
+            if self.namespaces[i].loggers == 0 {
+                self.namespaces.remove(i);
+            }
+        }
+    }
+}
+
+static LIVE_COLORS: Mutex<LiveColors> = Mutex::new(LiveColors {
+    namespaces: Vec::new(),
+});
+
+/// Picks a logger's color, returning whether it was registered as live and
+/// has to be handed back with `release_color` when the logger is dropped.
+pub(crate) fn assign_color(namespace: &str, fixed: Option<Rgb>) -> (Rgb, bool) {
+    let (assignment, palette) =
+        with_color_config(|config| (config.assignment, config.readable.clone()));
+    if assignment == ColorAssignment::Hashed {
+        return (fixed.unwrap_or_else(|| color_for_string(namespace)), false);
+    }
+
+    let fixed = fixed.or_else(|| override_from(|name| env::var(name).ok(), namespace));
+    let mut live = LIVE_COLORS.lock().unwrap_or_else(|e| e.into_inner());
+
+    (live.assign(namespace, &palette, fixed), true)
+}
+
+pub(crate) fn release_color(namespace: &str, color: Rgb) {
+    LIVE_COLORS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .release(namespace, color);
+}
+
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    // Grayscale approximation
+    if r == g && g == b {
+        if r < 8 {
+            return 16;
+        }
+        if r > 248 {
+            return 231;
+        }
+        return 232 + ((r as u16 - 8) * 24 / 247) as u8;
+    }
+
+    // RGB 6x6x6 Cube (16–231)
+    let r = scale_to_ansi(r);
+    let g = scale_to_ansi(g);
+    let b = scale_to_ansi(b);
+
+    16 + 36 * r + 6 * g + b
+}
+
+fn scale_to_ansi(value: u8) -> u8 {
+    match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        115..=154 => 2,
+        155..=194 => 3,
+        195..=234 => 4,
+        _ => 5,
+    }
+}
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    /// The 16 basic ANSI colors
+    Basic,
+    /// The xterm 256 color palette
+    Ansi256,
+    /// 24-bit RGB
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Detects the depth from `DEBUG_COLOR_DEPTH`, `FORCE_COLOR` levels,
+    /// `COLORTERM` and `TERM`, once per process.
+    pub fn detect() -> ColorDepth {
+        static DEPTH: OnceLock<ColorDepth> = OnceLock::new();
+
+        *DEPTH.get_or_init(|| depth_from(|name| env::var(name).ok()))
+    }
+}
+
+fn depth_from(var: impl Fn(&str) -> Option<String>) -> ColorDepth {
+    match var("DEBUG_COLOR_DEPTH").as_deref() {
+        Some("16") => return ColorDepth::Basic,
+        Some("256") => return ColorDepth::Ansi256,
+        Some("truecolor" | "24bit") => return ColorDepth::TrueColor,
+        _ => {}
+    }
+
+    // supports-color levels, as used by Node
+    match var("FORCE_COLOR").as_deref() {
+        Some("1") => return ColorDepth::Basic,
+        Some("2") => return ColorDepth::Ansi256,
+        Some("3") => return ColorDepth::TrueColor,
+        _ => {}
+    }
+
+    if matches!(var("COLORTERM").as_deref(), Some("truecolor" | "24bit"))
+        || var("WT_SESSION").is_some()
+    {
+        return ColorDepth::TrueColor;
+    }
+
+    match var("TERM") {
+        Some(term) if term.ends_with("-direct") || term.contains("truecolor") => {
+            ColorDepth::TrueColor
+        }
+        Some(term) if term.contains("256") => ColorDepth::Ansi256,
+        Some(term) if term.is_empty() => ColorDepth::Ansi256,
+        Some(_) => ColorDepth::Basic,
+        // No TERM at all is usually a Windows console, which handles 256 colors
+        None => ColorDepth::Ansi256,
+    }
+}
+
+pub(crate) fn colorize(color: Rgb, depth: ColorDepth, text: &str) -> String {
+    match depth {
+        ColorDepth::TrueColor => format!(
+            "\x1b[1;38;2;{};{};{}m{}\x1b[0m",
+            color.r, color.g, color.b, text
+        ),
+        ColorDepth::Ansi256 => format!("\x1b[1;38;5;{}m{}\x1b[0m", color.ansi256(), text),
+        ColorDepth::Basic => format!("\x1b[1;{}m{}\x1b[0m", color.basic(), text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn colors_match_debug_js() {
+        for code in COLORS {
+            assert_eq!(Rgb::from_xterm(code).ansi256(), code);
+        }
+        assert_eq!(
+            color_for_string("api:auth"),
+            Rgb::from_hex("#00d700").unwrap()
+        );
+
+        // Indexes computed with debug-js's selectColor in Node
+        assert_eq!(select_color("api:auth", COLORS.len()), 8);
+        assert_eq!(select_color("label", COLORS.len()), 36);
+        assert_eq!(select_color("db:read", COLORS.len()), 62);
+        assert_eq!(select_color("db:write", COLORS.len()), 23);
+        assert_eq!(select_color("héllo:😀", COLORS.len()), 31);
+        assert_eq!(
+            select_color(
+                "a-very-long-namespace-name-that-overflows:sub:sub",
+                COLORS.len()
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn themes_drop_colors_without_enough_contrast() {
+        let dark_blue = Rgb::from_xterm(20);
+        let yellow = Rgb::from_xterm(220);
+
+        let dark = ColorConfig::new(vec![], Some(Theme::Dark));
+        assert!(!dark.readable.contains(&dark_blue));
+        assert!(dark.readable.contains(&yellow));
+
+        let light = ColorConfig::new(vec![], Some(Theme::Light));
+        assert!(light.readable.contains(&dark_blue));
+        assert!(!light.readable.contains(&yellow));
+
+        let unreadable = ColorConfig::new(vec![Rgb::new(5, 5, 5)], Some(Theme::Dark));
+        assert_eq!(unreadable.readable, vec![Rgb::new(5, 5, 5)]);
+    }
+
+    #[test]
+    fn overrides_match_the_namespace_or_its_shell_safe_name() {
+        let orange = Some(Rgb::new(0xff, 0x88, 0x00));
+
+        assert_eq!(
+            override_from(vars(&[("DEBUG_COLOR_db", "#ff8800")]), "db"),
+            orange
+        );
+        assert_eq!(
+            override_from(vars(&[("DEBUG_COLOR_db:read", "ff8800")]), "db:read"),
+            orange
+        );
+        assert_eq!(
+            override_from(vars(&[("DEBUG_COLOR_db_read", "#ff8800")]), "db:read"),
+            orange
+        );
+        assert_eq!(
+            override_from(vars(&[("DEBUG_COLOR_db", "orange")]), "db"),
+            None
+        );
+    }
+
+    #[test]
+    fn distinct_colors_avoid_live_ones() {
+        let red = Rgb::new(255, 0, 0);
+        let dark_red = Rgb::new(200, 0, 0);
+        let green = Rgb::new(0, 255, 0);
+        let palette = [red, dark_red, green];
+        let mut live = LiveColors::default();
+
+        let first = live.assign("db:read", &palette, None);
+        assert_eq!(first, palette[select_color("db:read", 3)]);
+
+        let second = live.assign("db:write", &palette, None);
+        assert_ne!(second, first);
+        let third = live.assign("db:pool", &palette, None);
+        assert!(![first, second].contains(&third));
+
+        // Another logger for a live namespace shares its color
+        assert_eq!(live.assign("db:read", &palette, None), first);
+        live.release("db:read", first);
+        assert_eq!(live.namespaces.len(), 3);
+        live.release("db:read", first);
+        assert_eq!(live.namespaces.len(), 2);
+
+        // A fixed color wins over the namespace's allocated one, like in hashed mode
+        let orange = Rgb::new(255, 128, 0);
+        assert_eq!(live.assign("db:write", &palette, Some(orange)), orange);
+        assert_eq!(live.assign("db:write", &palette, None), second);
+        live.release("db:write", orange);
+        assert_eq!(live.namespaces.len(), 2);
+
+        // Same creation order, same colors
+        let mut again = LiveColors::default();
+        assert_eq!(again.assign("db:read", &palette, None), first);
+        assert_eq!(again.assign("db:write", &palette, None), second);
+    }
+
+    #[test]
+    fn distinct_colors_prefer_the_furthest_palette_entry() {
+        let palette = [
+            Rgb::new(255, 0, 0),
+            Rgb::new(200, 0, 0),
+            Rgb::new(0, 0, 255),
+        ];
+
+        assert_eq!(most_distinct(&palette, 1, &[]), palette[1]);
+        assert_eq!(most_distinct(&palette, 0, &[palette[1]]), palette[2]);
+        assert_eq!(most_distinct(&palette, 0, &[palette[2]]), palette[0]);
+    }
+
+    #[test]
+    fn detects_depth_from_env() {
+        assert_eq!(depth_from(vars(&[])), ColorDepth::Ansi256);
+        assert_eq!(depth_from(vars(&[("TERM", "xterm")])), ColorDepth::Basic);
+        assert_eq!(
+            depth_from(vars(&[("TERM", "xterm-256color")])),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            depth_from(vars(&[("TERM", "xterm"), ("COLORTERM", "truecolor")])),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            depth_from(vars(&[("COLORTERM", "truecolor"), ("FORCE_COLOR", "1")])),
+            ColorDepth::Basic
+        );
+        assert_eq!(
+            depth_from(vars(&[("FORCE_COLOR", "3"), ("DEBUG_COLOR_DEPTH", "256")])),
+            ColorDepth::Ansi256
+        );
+    }
+
+    #[test]
+    fn renders_each_depth() {
+        let red = Rgb::from_hex("#CC0033").unwrap();
+
+        assert_eq!(
+            colorize(red, ColorDepth::TrueColor, "db"),
+            "\x1b[1;38;2;204;0;51mdb\x1b[0m"
+        );
+        assert_eq!(
+            colorize(red, ColorDepth::Ansi256, "db"),
+            "\x1b[1;38;5;161mdb\x1b[0m"
+        );
+        assert_eq!(
+            colorize(red, ColorDepth::Basic, "db"),
+            "\x1b[1;31mdb\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn basic_colors_are_the_nearest_match() {
+        let basic = |hex| Rgb::from_hex(hex).unwrap().basic();
+
+        assert_eq!(basic("#0000FF"), 34);
+        assert_eq!(basic("#00CC00"), 32);
+        assert_eq!(basic("#FFCC33"), 33);
+        assert_eq!(basic("#33CCCC"), 36);
+    }
+}
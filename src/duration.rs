@@ -0,0 +1,118 @@
+use std::{env, sync::OnceLock, time::Duration};
+
+/// The smallest unit a `+diff` is shown in. Anything shorter rounds down to it,
+/// so with `Millis` a 350µs gap shows as `+0ms`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiffPrecision {
+    Nanos,
+    #[default]
+    Micros,
+    Millis,
+}
+
+impl DiffPrecision {
+    // DEBUG_DIFF_PRECISION=ns|us|ms sets it for loggers without an explicit precision
+    pub(crate) fn from_env() -> DiffPrecision {
+        static PRECISION: OnceLock<DiffPrecision> = OnceLock::new();
+
+        *PRECISION.get_or_init(|| match env::var("DEBUG_DIFF_PRECISION").as_deref() {
+            Ok("ns") => DiffPrecision::Nanos,
+            Ok("ms") => DiffPrecision::Millis,
+            _ => DiffPrecision::Micros,
+        })
+    }
+}
+
+/// Which earlier line a `+diff` is measured from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffMode {
+    /// The previous line of the same logger, like Node's debug.
+    #[default]
+    Namespace,
+    /// The previous line of any logger in this mode.
+    Global,
+    /// The previous line in this namespace or any namespace below it, so
+    /// `app` counts lines of `app:db`, but `app:db` doesn't count `app`'s.
+    Subtree,
+}
+
+impl DiffMode {
+    // DEBUG_DIFF=namespace|global|subtree sets it for loggers without an explicit mode
+    pub(crate) fn from_env() -> DiffMode {
+        static MODE: OnceLock<DiffMode> = OnceLock::new();
+
+        *MODE.get_or_init(|| match env::var("DEBUG_DIFF").as_deref() {
+            Ok("global") => DiffMode::Global,
+            Ok("subtree") => DiffMode::Subtree,
+            _ => DiffMode::Namespace,
+        })
+    }
+}
+
+const SECOND: f64 = 1_000.0;
+const MINUTE: f64 = SECOND * 60.0;
+const HOUR: f64 = MINUTE * 60.0;
+const DAY: f64 = HOUR * 24.0;
+
+/// Formats a diff the way Node's `ms` does, `+12ms`, `+3m`, `+1h`, with a decimal
+/// for single-digit seconds (`+2.4s`) and sub-millisecond units down to `precision`.
+pub fn humanize(elapsed: Duration, precision: DiffPrecision) -> String {
+    let nanos = elapsed.as_nanos();
+    if nanos < 1_000 && precision == DiffPrecision::Nanos {
+        return format!("+{}ns", nanos);
+    }
+    if nanos < 1_000_000 && precision <= DiffPrecision::Micros {
+        return format!("+{}µs", nanos / 1_000);
+    }
+
+    let ms = elapsed.as_secs_f64() * 1_000.0;
+    if ms >= DAY {
+        format!("+{}d", (ms / DAY).round())
+    } else if ms >= HOUR {
+        format!("+{}h", (ms / HOUR).round())
+    } else if ms >= MINUTE {
+        format!("+{}m", (ms / MINUTE).round())
+    } else if ms >= 10.0 * SECOND {
+        format!("+{}s", (ms / SECOND).round())
+    } else if ms >= SECOND {
+        let seconds = format!("{:.1}", ms / SECOND);
+        format!("+{}s", seconds.trim_end_matches(".0"))
+    } else {
+        format!("+{}ms", elapsed.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn humanizes_like_node_ms() {
+        let humanize_ms = |ms| humanize(Duration::from_millis(ms), DiffPrecision::Micros);
+
+        assert_eq!(humanize_ms(12), "+12ms");
+        assert_eq!(humanize_ms(2_400), "+2.4s");
+        assert_eq!(humanize_ms(2_000), "+2s");
+        assert_eq!(humanize_ms(42_000), "+42s");
+        assert_eq!(humanize_ms(180_000), "+3m");
+        assert_eq!(humanize_ms(3_600_000), "+1h");
+        assert_eq!(humanize_ms(3 * 86_400_000), "+3d");
+    }
+
+    #[test]
+    fn precision_sets_the_smallest_unit() {
+        let gap = Duration::from_nanos(350_250);
+
+        assert_eq!(humanize(gap, DiffPrecision::Millis), "+0ms");
+        assert_eq!(humanize(gap, DiffPrecision::Micros), "+350µs");
+        assert_eq!(humanize(gap, DiffPrecision::Nanos), "+350µs");
+        assert_eq!(
+            humanize(Duration::from_nanos(250), DiffPrecision::Nanos),
+            "+250ns"
+        );
+        assert_eq!(
+            humanize(Duration::from_nanos(250), DiffPrecision::Micros),
+            "+0µs"
+        );
+    }
+}
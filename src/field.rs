@@ -0,0 +1,142 @@
+use std::fmt::{self, Debug, Display};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Str(String),
+}
+
+impl Value {
+    // `key = %value` in the macros
+    pub fn display(value: &impl Display) -> Self {
+        Value::Str(value.to_string())
+    }
+
+    // `key = ?value` in the macros
+    pub fn debug(value: &impl Debug) -> Self {
+        Value::Str(format!("{:?}", value))
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::I64(value) => write!(f, "{}", value),
+            Value::U64(value) => write!(f, "{}", value),
+            Value::F64(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident($inner:ty): $($from:ty),+) => {
+        $(
+            impl From<$from> for Value {
+                fn from(value: $from) -> Self {
+                    Value::$variant(<$inner>::from(value))
+                }
+            }
+        )+
+    };
+}
+
+impl_from!(Bool(bool): bool);
+impl_from!(I64(i64): i8, i16, i32, i64);
+impl_from!(U64(u64): u8, u16, u32, u64);
+impl_from!(F64(f64): f32, f64);
+impl_from!(Str(String): &str, String, &String, char);
+
+impl From<isize> for Value {
+    fn from(value: isize) -> Self {
+        Value::I64(value as i64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::U64(value as u64)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub key: &'static str,
+    pub value: Value,
+}
+
+impl Field {
+    pub fn new(key: &'static str, value: impl Into<Value>) -> Self {
+        Field {
+            key,
+            value: value.into(),
+        }
+    }
+}
+
+// `key=value`, quoting strings that would otherwise be ambiguous to read back
+impl Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Value::Str(value)
+                if value.is_empty()
+                    || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') =>
+            {
+                write!(f, "{}={:?}", self.key, value)
+            }
+            value => write!(f, "{}={}", self.key, value),
+        }
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __dbug_fields {
+    (@ [$($out:expr),*]) => {
+        [$($out),*]
+    };
+    (@ [$($out:expr),*] $key:ident = % $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__dbug_fields!(@ [$($out,)* $crate::Field::new(stringify!($key), $crate::Value::display(&$value))] $($($rest)*)?)
+    };
+    (@ [$($out:expr),*] $key:ident = ? $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__dbug_fields!(@ [$($out,)* $crate::Field::new(stringify!($key), $crate::Value::debug(&$value))] $($($rest)*)?)
+    };
+    (@ [$($out:expr),*] $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__dbug_fields!(@ [$($out,)* $crate::Field::new(stringify!($key), $value)] $($($rest)*)?)
+    };
+    ($($fields:tt)+) => {
+        $crate::__dbug_fields!(@ [] $($fields)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_macro_supports_display_and_debug_sigils() {
+        let path = "/users";
+        let fields = crate::__dbug_fields!(status = 200, path = %path, tags = ?["a"], ok = true,);
+
+        assert_eq!(
+            fields,
+            [
+                Field::new("status", 200),
+                Field::new("path", "/users"),
+                Field::new("tags", "[\"a\"]"),
+                Field::new("ok", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn string_values_are_quoted_when_ambiguous() {
+        assert_eq!(Field::new("path", "/users").to_string(), "path=/users");
+        assert_eq!(Field::new("msg", "a b").to_string(), "msg=\"a b\"");
+        assert_eq!(Field::new("empty", "").to_string(), "empty=\"\"");
+    }
+}
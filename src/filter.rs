@@ -0,0 +1,271 @@
+use std::sync::{
+    RwLock,
+    atomic::{AtomicU64, Ordering},
+};
+
+use crate::Level;
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Token {
+    Char(char),
+    AnyChar,
+    AnySequence,
+}
+
+// A single DEBUG namespace glob: `*` matches any run of characters, `?` exactly one
+pub(crate) enum Pattern {
+    Exact(String),
+    Glob(Vec<Token>),
+}
+
+impl Pattern {
+    pub(crate) fn compile(pattern: &str) -> Self {
+        if !pattern.contains(['*', '?']) {
+            return Pattern::Exact(pattern.to_string());
+        }
+
+        let mut tokens = Vec::with_capacity(pattern.len());
+        for c in pattern.chars() {
+            let token = match c {
+                '*' => Token::AnySequence,
+                '?' => Token::AnyChar,
+                c => Token::Char(c),
+            };
+
+            // `**` means the same as `*`
+            if token == Token::AnySequence && tokens.last() == Some(&Token::AnySequence) {
+                continue;
+            }
+
+            tokens.push(token);
+        }
+
+        Pattern::Glob(tokens)
+    }
+
+    pub(crate) fn matches(&self, namespace: &str) -> bool {
+        let tokens = match self {
+            Pattern::Exact(exact) => return exact == namespace,
+            Pattern::Glob(tokens) => tokens,
+        };
+
+        let next_len = |at: usize| namespace[at..].chars().next().map_or(0, char::len_utf8);
+
+        let mut token = 0;
+        let mut at = 0;
+        // Where to resume if the current attempt fails: the token after the last `*`,
+        // and the position in the namespace that `*` has consumed up to
+        let mut backtrack = None;
+
+        while at < namespace.len() {
+            let c = namespace[at..].chars().next().unwrap_or_default();
+
+            match tokens.get(token) {
+                Some(Token::Char(expected)) if *expected == c => {
+                    token += 1;
+                    at += c.len_utf8();
+                }
+                Some(Token::AnyChar) => {
+                    token += 1;
+                    at += c.len_utf8();
+                }
+                Some(Token::AnySequence) => {
+                    token += 1;
+                    backtrack = Some((token, at));
+                }
+                _ => match backtrack {
+                    Some((resume, consumed)) => {
+                        let consumed = consumed + next_len(consumed);
+                        backtrack = Some((resume, consumed));
+                        token = resume;
+                        at = consumed;
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        tokens[token..].iter().all(|t| *t == Token::AnySequence)
+    }
+}
+
+// Namespaces are enabled when they match any pattern and none of the `-` skips,
+// no matter which order the two appear in. When several patterns with a `=level`
+// match, the last one sets the threshold.
+pub(crate) struct Filter {
+    source: Vec<String>,
+    names: Vec<(Pattern, Level)>,
+    skips: Vec<Pattern>,
+}
+
+impl Filter {
+    pub(crate) fn parse(namespaces: &str) -> Self {
+        let source: Vec<String> = namespaces
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+
+        let mut names = vec![];
+        let mut skips = vec![];
+        for directive in &source {
+            let (pattern, level) = match directive.rsplit_once("=") {
+                Some((pattern, level)) => match level.parse() {
+                    Ok(level) => (pattern, level),
+                    Err(_) => (directive.as_str(), Level::Trace),
+                },
+                None => (directive.as_str(), Level::Trace),
+            };
+
+            match pattern.strip_prefix("-") {
+                Some(skip) => skips.push(Pattern::compile(skip)),
+                None => names.push((Pattern::compile(pattern), level)),
+            }
+        }
+
+        Filter {
+            source,
+            names,
+            skips,
+        }
+    }
+
+    fn from_env() -> Self {
+        Filter::parse(&std::env::var("DEBUG").unwrap_or_default())
+    }
+
+    pub(crate) fn threshold(&self, namespace: &str) -> Option<Level> {
+        if self.skips.iter().any(|skip| skip.matches(namespace)) {
+            return None;
+        }
+
+        self.names
+            .iter()
+            .rev()
+            .find(|(name, _)| name.matches(namespace))
+            .map(|(_, level)| *level)
+    }
+
+    pub(crate) fn enabled(&self, namespace: &str) -> bool {
+        self.threshold(namespace).is_some()
+    }
+
+    pub(crate) fn namespaces(&self) -> String {
+        self.source.join(",")
+    }
+}
+
+// None until the first lookup reads DEBUG
+static FILTER: RwLock<Option<Filter>> = RwLock::new(None);
+
+// Bumped on every change so loggers know their cached answer is stale
+static GENERATION: AtomicU64 = AtomicU64::new(1);
+
+pub(crate) fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+pub(crate) fn with_filter<R>(f: impl FnOnce(&Filter) -> R) -> R {
+    if let Ok(filter) = FILTER.read()
+        && let Some(filter) = filter.as_ref()
+    {
+        return f(filter);
+    }
+
+    let mut filter = FILTER.write().unwrap_or_else(|e| e.into_inner());
+    f(filter.get_or_insert_with(Filter::from_env))
+}
+
+fn replace(filter: Filter) -> Option<Filter> {
+    let mut current = FILTER.write().unwrap_or_else(|e| e.into_inner());
+    let previous = current.replace(filter);
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+
+    previous
+}
+
+/// Replaces the active namespaces, using the same syntax as `DEBUG`.
+pub fn enable(namespaces: &str) {
+    replace(Filter::parse(namespaces));
+}
+
+/// Turns every namespace off and returns the namespaces that were enabled.
+pub fn disable() -> String {
+    replace(Filter::parse(""))
+        .unwrap_or_else(Filter::from_env)
+        .namespaces()
+}
+
+pub fn enabled(namespace: &str) -> bool {
+    with_filter(|filter| filter.enabled(namespace))
+}
+
+pub(crate) fn threshold(namespace: &str) -> Option<Level> {
+    with_filter(|filter| filter.threshold(namespace))
+}
+
+// Tests that touch the process-wide filter take this lock first
+#[cfg(test)]
+pub(crate) fn lock_for_test() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negations_win_over_matches() {
+        let filter = Filter::parse("*,-label*");
+
+        assert!(filter.enabled("something"));
+        assert!(!filter.enabled("label"));
+        assert!(!filter.enabled("label:extended"));
+    }
+
+    #[test]
+    fn globs_match_anywhere_in_the_namespace() {
+        let filter = Filter::parse("app:*:db, a*b*c  worker:?");
+
+        assert!(filter.enabled("app:users:db"));
+        assert!(filter.enabled("app:a:b:db"));
+        assert!(!filter.enabled("app:users:dbx"));
+        assert!(filter.enabled("abc"));
+        assert!(filter.enabled("axxbyyc"));
+        assert!(!filter.enabled("axxbyy"));
+        assert!(filter.enabled("worker:1"));
+        assert!(!filter.enabled("worker:12"));
+        assert!(!filter.enabled("worker:"));
+    }
+
+    #[test]
+    fn negations_apply_regardless_of_order() {
+        let filter = Filter::parse("-app:*:cache app:*");
+
+        assert!(filter.enabled("app:users"));
+        assert!(!filter.enabled("app:users:cache"));
+    }
+
+    #[test]
+    fn levels_set_per_namespace_thresholds() {
+        let filter = Filter::parse("db:*=warn,http=TRACE,*=info,db:slow=error,app=nope");
+
+        assert_eq!(filter.threshold("db:read"), Some(Level::Info));
+        assert_eq!(filter.threshold("db:slow"), Some(Level::Error));
+        assert_eq!(filter.threshold("http"), Some(Level::Info));
+        assert_eq!(filter.threshold("app=nope"), Some(Level::Trace));
+
+        let filter = Filter::parse("*=info,db:*=warn,http=trace,-http:noisy=warn");
+        assert_eq!(filter.threshold("db:read"), Some(Level::Warn));
+        assert_eq!(filter.threshold("http"), Some(Level::Trace));
+        assert_eq!(filter.threshold("http:noisy"), None);
+    }
+
+    #[test]
+    fn namespaces_round_trip() {
+        assert_eq!(Filter::parse("a b,-c").namespaces(), "a,b,-c");
+        assert_eq!(Filter::parse("").namespaces(), "");
+    }
+}
@@ -0,0 +1,512 @@
+use std::{
+    borrow::Cow,
+    env,
+    fmt::Write,
+    sync::OnceLock,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    ColorDepth, DiffPrecision, Field, Level, Template, Value,
+    color::{Rgb, colorize},
+    duration::humanize,
+    hyperlink::hyperlink,
+    timestamp::{self, TimePrecision, Timestamp},
+};
+
+/// Where in the source a log call was made. Only the macros know the module.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    pub file: &'static str,
+    pub line: u32,
+    pub module: Option<&'static str>,
+}
+
+impl From<&'static std::panic::Location<'static>> for Location {
+    fn from(location: &'static std::panic::Location<'static>) -> Self {
+        Location {
+            file: location.file(),
+            line: location.line(),
+            module: None,
+        }
+    }
+}
+
+/// Whether the human format shows where each line was logged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LocationStyle {
+    #[default]
+    Hidden,
+    /// `file:line` after the message
+    Plain,
+    /// `file:line` as an OSC 8 link to the file when the sink has colors,
+    /// opened with `DEBUG_EDITOR_URL` if set
+    Hyperlink,
+}
+
+impl LocationStyle {
+    // DEBUG_LOCATION=1|link sets it for loggers without an explicit one
+    pub(crate) fn from_env() -> LocationStyle {
+        static STYLE: OnceLock<LocationStyle> = OnceLock::new();
+
+        *STYLE.get_or_init(|| match env::var("DEBUG_LOCATION").as_deref() {
+            Ok("link" | "hyperlink") => LocationStyle::Hyperlink,
+            Ok("1" | "true" | "on" | "plain") => LocationStyle::Plain,
+            _ => LocationStyle::Hidden,
+        })
+    }
+}
+
+impl Location {
+    pub(crate) fn render(&self, style: LocationStyle, colors: Option<ColorDepth>) -> String {
+        let text = format!("{}:{}", self.file, self.line);
+        match (style, colors) {
+            (LocationStyle::Hyperlink, Some(_)) => hyperlink(self, &text),
+            _ => text,
+        }
+    }
+}
+
+/// Everything known about a single log call, handed to a `Format` to render.
+pub(crate) struct Record<'a> {
+    pub(crate) namespace: &'a str,
+    pub(crate) level: Option<Level>,
+    pub(crate) message: &'a str,
+    pub(crate) fields: &'a [Field],
+    /// Time since this namespace's previous log, `None` for its first one.
+    pub(crate) elapsed: Option<Duration>,
+    pub(crate) timestamp: SystemTime,
+    pub(crate) thread: String,
+    pub(crate) pid: u32,
+    pub(crate) location: Option<Location>,
+    /// Time since the process started, shared by every logger.
+    pub(crate) uptime: Duration,
+    pub(crate) color: Rgb,
+    pub(crate) precision: DiffPrecision,
+    pub(crate) multiline: Multiline,
+    pub(crate) location_style: LocationStyle,
+    pub(crate) timestamp_style: Timestamp,
+    pub(crate) time_precision: TimePrecision,
+    pub(crate) show_uptime: bool,
+    pub(crate) single_line: bool,
+}
+
+impl<'a> Record<'a> {
+    pub(crate) fn new(
+        namespace: &'a str,
+        level: Option<Level>,
+        message: &'a str,
+        fields: &'a [Field],
+    ) -> Self {
+        let current = thread::current();
+        let thread = match current.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", current.id()),
+        };
+
+        Record {
+            namespace,
+            level,
+            message,
+            fields,
+            elapsed: None,
+            timestamp: SystemTime::now(),
+            thread,
+            pid: std::process::id(),
+            location: None,
+            uptime: Duration::ZERO,
+            color: Rgb { r: 0, g: 0, b: 0 },
+            precision: DiffPrecision::default(),
+            multiline: Multiline::default(),
+            location_style: LocationStyle::default(),
+            timestamp_style: Timestamp::default(),
+            time_precision: TimePrecision::default(),
+            show_uptime: false,
+            single_line: false,
+        }
+    }
+}
+
+/// How the human format lays out messages that span several lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Multiline {
+    /// Every line starts with the namespace, like Node's debug.
+    #[default]
+    Prefix,
+    /// Only the first line has the namespace, the rest are indented under it.
+    Indent,
+}
+
+impl Multiline {
+    // DEBUG_MULTILINE=prefix|indent sets it for loggers without an explicit one
+    pub(crate) fn from_env() -> Multiline {
+        static MULTILINE: OnceLock<Multiline> = OnceLock::new();
+
+        *MULTILINE.get_or_init(|| match env::var("DEBUG_MULTILINE").as_deref() {
+            Ok("indent") => Multiline::Indent,
+            _ => Multiline::Prefix,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Format {
+    /// `namespace message +ms` with a colored namespace
+    #[default]
+    Human,
+    /// One JSON object per line
+    Json,
+    Template(Template),
+}
+
+impl Format {
+    // DEBUG_FORMAT=json or DEBUG_FORMAT="<template>" switches every logger
+    // without an explicit format. A template that doesn't parse is ignored.
+    pub(crate) fn from_env() -> &'static Format {
+        static FORMAT: OnceLock<Format> = OnceLock::new();
+
+        FORMAT.get_or_init(|| match env::var("DEBUG_FORMAT") {
+            Ok(format) if format.eq_ignore_ascii_case("json") => Format::Json,
+            Ok(format) if format.eq_ignore_ascii_case("human") => Format::Human,
+            Ok(template) => Template::parse(&template)
+                .map(Format::Template)
+                .unwrap_or_default(),
+            Err(_) => Format::Human,
+        })
+    }
+
+    // Renders `record` as a single line, with ANSI colors of the given depth
+    // if `colors` is set
+    pub(crate) fn render(&self, record: &Record, colors: Option<ColorDepth>) -> String {
+        match self {
+            Format::Human => match colors {
+                Some(depth) => render_human(record, depth),
+                None => render_plain(record),
+            },
+            Format::Json => render_json(record),
+            Format::Template(template) => template.render(record, colors),
+        }
+    }
+}
+
+// `prefix` starts every line of the message, or only the first one with
+// `Multiline::Indent`, where the others get `width` spaces instead. The diff
+// stays on the first line and the fields and location go after the last one.
+fn push_message(
+    line: &mut String,
+    record: &Record,
+    mut prefix: String,
+    mut width: usize,
+    diff: Option<String>,
+    colors: Option<ColorDepth>,
+) {
+    if let Some(level) = record.level {
+        let _ = write!(prefix, " {:<5}", level);
+        width += 6;
+    }
+
+    let message = record.message();
+    let mut lines = message.lines();
+    let _ = write!(line, "{} {}", prefix, lines.next().unwrap_or_default());
+    let rest: Vec<&str> = lines.collect();
+    if let Some(diff) = diff.as_ref().filter(|_| !rest.is_empty()) {
+        let _ = write!(line, " {}", diff);
+    }
+
+    for text in &rest {
+        line.push('\n');
+        match record.multiline {
+            Multiline::Prefix => line.push_str(&prefix),
+            Multiline::Indent => line.extend(std::iter::repeat_n(' ', width)),
+        }
+        let _ = write!(line, " {}", text);
+    }
+
+    for field in record.fields {
+        let _ = write!(line, " {}", field);
+    }
+    if record.location_style != LocationStyle::Hidden
+        && let Some(location) = record.location
+    {
+        let _ = write!(line, " {}", location.render(record.location_style, colors));
+    }
+    if let Some(diff) = diff.filter(|_| rest.is_empty()) {
+        let _ = write!(line, " {}", diff);
+    }
+}
+
+impl Record<'_> {
+    // The message, escaped onto one line for sinks that need that
+    pub(crate) fn message(&self) -> Cow<'_, str> {
+        if self.single_line && self.message.contains(['\n', '\r']) {
+            Cow::Owned(self.message.replace('\r', "\\r").replace('\n', "\\n"))
+        } else {
+            Cow::Borrowed(self.message)
+        }
+    }
+
+    // The timestamp as configured, UTC when timestamps are off
+    pub(crate) fn time(&self) -> String {
+        timestamp::render(self.timestamp, self.timestamp_style, self.time_precision)
+    }
+
+    pub(crate) fn uptime(&self) -> String {
+        timestamp::uptime(self.uptime, self.time_precision)
+    }
+
+    // The timestamp and uptime columns that start a line, if turned on
+    fn times(&self, timestamp: bool) -> String {
+        let mut times = String::new();
+        if timestamp {
+            let _ = write!(times, "{} ", self.time());
+        }
+        if self.show_uptime {
+            let _ = write!(times, "{} ", self.uptime());
+        }
+
+        times
+    }
+}
+
+fn render_human(record: &Record, depth: ColorDepth) -> String {
+    let ms_diff = match record.elapsed {
+        Some(elapsed) => humanize(elapsed, record.precision),
+        None => "+0ms".to_string(),
+    };
+    let times = record.times(record.timestamp_style != Timestamp::Off);
+
+    let mut line = String::with_capacity(64 + record.message.len());
+    push_message(
+        &mut line,
+        record,
+        format!(
+            "{}{}",
+            times,
+            colorize(record.color, depth, record.namespace)
+        ),
+        times.chars().count() + record.namespace.chars().count(),
+        Some(colorize(record.color, depth, &ms_diff)),
+        Some(depth),
+    );
+
+    line
+}
+
+// Node's format when colors are off: an ISO timestamp in place of the diff
+fn render_plain(record: &Record) -> String {
+    let prefix = format!("{}{}", record.times(true), record.namespace);
+    let width = prefix.chars().count();
+
+    let mut line = String::with_capacity(64 + record.message.len());
+    push_message(&mut line, record, prefix, width, None, None);
+
+    line
+}
+
+fn render_json(record: &Record) -> String {
+    let mut line = String::with_capacity(128 + record.message.len());
+
+    line.push_str("{\"namespace\":");
+    push_json_string(&mut line, record.namespace);
+    if let Some(level) = record.level {
+        line.push_str(",\"level\":");
+        push_json_string(&mut line, &level.as_str().to_lowercase());
+    }
+    line.push_str(",\"message\":");
+    push_json_string(&mut line, record.message);
+    if !record.fields.is_empty() {
+        line.push_str(",\"fields\":{");
+        for (i, field) in record.fields.iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            push_json_string(&mut line, field.key);
+            line.push(':');
+            push_json_value(&mut line, &field.value);
+        }
+        line.push('}');
+    }
+    let elapsed_ms = record.elapsed.unwrap_or_default().as_micros() as f64 / 1000.0;
+    let _ = write!(line, ",\"elapsed_ms\":{}", elapsed_ms);
+    line.push_str(",\"timestamp\":");
+    push_json_string(&mut line, &record.time());
+    if record.show_uptime {
+        let _ = write!(
+            line,
+            ",\"uptime_ms\":{}",
+            record.uptime.as_micros() as f64 / 1000.0
+        );
+    }
+    line.push_str(",\"thread\":");
+    push_json_string(&mut line, &record.thread);
+    let _ = write!(line, ",\"pid\":{}", record.pid);
+    if let Some(location) = record.location {
+        line.push_str(",\"file\":");
+        push_json_string(&mut line, location.file);
+        let _ = write!(line, ",\"line\":{}", location.line);
+        if let Some(module) = location.module {
+            line.push_str(",\"module\":");
+            push_json_string(&mut line, module);
+        }
+    }
+    line.push('}');
+
+    line
+}
+
+fn push_json_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Str(value) => push_json_string(out, value),
+        // JSON has no NaN or infinity
+        Value::F64(value) if !value.is_finite() => out.push_str("null"),
+        value => {
+            let _ = write!(out, "{}", value);
+        }
+    }
+}
+
+pub(crate) fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn json_lines_escape_and_carry_every_field() {
+        let mut record = Record::new(
+            "http:server",
+            Some(Level::Warn),
+            "said \"hi\"\n\tand left \u{1b}",
+            &[],
+        );
+        record.elapsed = Some(Duration::from_micros(12_345));
+        record.timestamp = UNIX_EPOCH;
+        record.thread = "main".into();
+        record.pid = 42;
+
+        assert_eq!(
+            Format::Json.render(&record, Some(ColorDepth::TrueColor)),
+            "{\"namespace\":\"http:server\",\"level\":\"warn\",\
+             \"message\":\"said \\\"hi\\\"\\n\\tand left \\u001b\",\
+             \"elapsed_ms\":12.345,\"timestamp\":\"1970-01-01T00:00:00.000Z\",\
+             \"thread\":\"main\",\"pid\":42}"
+        );
+    }
+
+    #[test]
+    fn multiline_messages_prefix_or_indent_every_line() {
+        let fields = [Field::new("rows", 2)];
+        let mut record = Record::new("db", Some(Level::Info), "Row {\n    id: 1,\n}\n", &fields);
+        record.elapsed = Some(Duration::from_millis(5));
+        record.timestamp = UNIX_EPOCH;
+        record.color = Rgb::from_hex("#0000CC").unwrap();
+
+        let db = "\x1b[1;38;5;20mdb\x1b[0m";
+        let diff = "\x1b[1;38;5;20m+5ms\x1b[0m";
+        assert_eq!(
+            Format::Human.render(&record, Some(ColorDepth::Ansi256)),
+            format!("{db} INFO  Row {{ {diff}\n{db} INFO      id: 1,\n{db} INFO  }} rows=2")
+        );
+        assert_eq!(
+            Format::Human.render(&record, None),
+            "1970-01-01T00:00:00.000Z db INFO  Row {\n\
+             1970-01-01T00:00:00.000Z db INFO      id: 1,\n\
+             1970-01-01T00:00:00.000Z db INFO  } rows=2"
+        );
+
+        record.multiline = Multiline::Indent;
+        assert_eq!(
+            Format::Human.render(&record, Some(ColorDepth::Ansi256)),
+            format!("{db} INFO  Row {{ {diff}\n             id: 1,\n         }} rows=2")
+        );
+    }
+
+    #[test]
+    fn timestamps_and_uptime_start_the_line() {
+        let mut record = Record::new("db", None, "ready", &[]);
+        record.timestamp = UNIX_EPOCH + Duration::from_micros(1_500_250);
+        record.uptime = Duration::from_micros(2_345_678);
+        record.color = Rgb::from_hex("#0000CC").unwrap();
+        record.elapsed = Some(Duration::from_millis(3));
+
+        assert!(
+            Format::Human
+                .render(&record, Some(ColorDepth::Ansi256))
+                .starts_with("\x1b[1;38;5;20mdb")
+        );
+
+        record.timestamp_style = Timestamp::Utc;
+        record.time_precision = TimePrecision::Micros;
+        record.show_uptime = true;
+        assert!(
+            Format::Human
+                .render(&record, Some(ColorDepth::Ansi256))
+                .starts_with("1970-01-01T00:00:01.500250Z 2.345678s \x1b[1;38;5;20mdb")
+        );
+        assert_eq!(
+            Format::Human.render(&record, None),
+            "1970-01-01T00:00:01.500250Z 2.345678s db ready"
+        );
+        assert!(
+            Format::Json
+                .render(&record, None)
+                .contains(",\"timestamp\":\"1970-01-01T00:00:01.500250Z\",\"uptime_ms\":2345.678,")
+        );
+    }
+
+    #[test]
+    fn fields_render_as_pairs_and_json_members() {
+        let fields = [
+            Field::new("status", 200),
+            Field::new("path", "/a b"),
+            Field::new("ratio", f64::NAN),
+        ];
+        let record = Record::new("http", None, "request done", &fields);
+
+        assert!(
+            Format::Human
+                .render(&record, Some(ColorDepth::Ansi256))
+                .contains(" request done status=200 path=\"/a b\" ratio=NaN ")
+        );
+        assert!(
+            Format::Json
+                .render(&record, None)
+                .contains(",\"fields\":{\"status\":200,\"path\":\"/a b\",\"ratio\":null},")
+        );
+    }
+
+    #[test]
+    fn plain_format_has_a_timestamp_and_no_escapes() {
+        let mut record = Record::new("http", Some(Level::Info), "listening", &[]);
+        record.elapsed = Some(Duration::from_millis(5));
+        record.timestamp = UNIX_EPOCH;
+        record.color = Rgb::from_hex("#0000CC").unwrap();
+
+        assert_eq!(
+            Format::Human.render(&record, None),
+            "1970-01-01T00:00:00.000Z http INFO  listening"
+        );
+        assert_eq!(
+            Format::Human.render(&record, Some(ColorDepth::Ansi256)),
+            "\x1b[1;38;5;20mhttp\x1b[0m INFO  listening \x1b[1;38;5;20m+5ms\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::Location;
+
+// `file!()` is relative to the directory cargo ran rustc in, which is usually
+// the one the program runs in too
+fn absolute(file: &str, cwd: Option<&Path>) -> PathBuf {
+    match cwd {
+        Some(cwd) if Path::new(file).is_relative() => cwd.join(file),
+        _ => PathBuf::from(file),
+    }
+}
+
+// DEBUG_EDITOR_URL="vscode://file{path}:{line}" opens links in an editor,
+// without it they are file:// URLs
+fn url_from(editor: Option<&str>, cwd: Option<&Path>, location: &Location) -> String {
+    let path = absolute(location.file, cwd)
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace(' ', "%20");
+
+    match editor {
+        Some(template) => template
+            .replace("{path}", &path)
+            .replace("{line}", &location.line.to_string()),
+        None if path.starts_with('/') => format!("file://{}", path),
+        None => format!("file:///{}", path),
+    }
+}
+
+/// Wraps `text` in an OSC 8 escape linking to the location's file.
+pub(crate) fn hyperlink(location: &Location, text: &str) -> String {
+    static EDITOR: OnceLock<Option<String>> = OnceLock::new();
+    static CWD: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+    let editor = EDITOR.get_or_init(|| env::var("DEBUG_EDITOR_URL").ok());
+    let cwd = CWD.get_or_init(|| env::current_dir().ok());
+    let url = url_from(editor.as_deref(), cwd.as_deref(), location);
+
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_to_files_or_editors() {
+        let location = Location {
+            file: "src/my db.rs",
+            line: 42,
+            module: None,
+        };
+        let cwd = Some(Path::new("/home/ada/app"));
+
+        assert_eq!(
+            url_from(None, cwd, &location),
+            "file:///home/ada/app/src/my%20db.rs"
+        );
+        assert_eq!(
+            url_from(Some("vscode://file{path}:{line}"), cwd, &location),
+            "vscode://file/home/ada/app/src/my%20db.rs:42"
+        );
+
+        let dependency = Location {
+            file: "/cargo/registry/src/lib.rs",
+            ..location
+        };
+        assert_eq!(
+            url_from(None, cwd, &dependency),
+            "file:///cargo/registry/src/lib.rs"
+        );
+    }
+}
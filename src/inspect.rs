@@ -0,0 +1,363 @@
+use std::{
+    env,
+    fmt::{self, Write},
+    sync::OnceLock,
+};
+
+#[derive(Clone, Copy, Debug)]
+struct Limits {
+    depth: usize,
+    max_items: usize,
+    max_string: usize,
+}
+
+impl Limits {
+    // DEBUG_DEPTH, DEBUG_MAX_ARRAY_LENGTH and DEBUG_MAX_STRING_LENGTH, the
+    // variables Node's debug turns into `util.inspect` options, with its defaults
+    fn from_env() -> Limits {
+        static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+        *LIMITS.get_or_init(|| limits_from(|name| env::var(name).ok()))
+    }
+}
+
+fn limits_from(var: impl Fn(&str) -> Option<String>) -> Limits {
+    let number = |name, default| {
+        var(name)
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(default)
+    };
+
+    Limits {
+        depth: number("DEBUG_DEPTH", 2),
+        max_items: number("DEBUG_MAX_ARRAY_LENGTH", 100),
+        max_string: number("DEBUG_MAX_STRING_LENGTH", 10_000),
+    }
+}
+
+/// A `Debug` value printed with Node's `%o`/`%O` limits, see `inspect`.
+pub struct Inspect<'a, T: ?Sized> {
+    value: &'a T,
+    pretty: bool,
+    limits: Limits,
+}
+
+/// Wraps `value` so it displays as its single-line `{:?}` output, like Node's
+/// `%o`, cut down to size: values nested deeper than `DEBUG_DEPTH` (2) become
+/// `…`, lists and maps stop after `DEBUG_MAX_ARRAY_LENGTH` (100) items and
+/// strings after `DEBUG_MAX_STRING_LENGTH` (10000) characters.
+pub fn inspect<T: fmt::Debug + ?Sized>(value: &T) -> Inspect<'_, T> {
+    Inspect {
+        value,
+        pretty: false,
+        limits: Limits::from_env(),
+    }
+}
+
+/// Like `inspect`, but with the multi-line `{:#?}` output, like Node's `%O`.
+pub fn inspect_pretty<T: fmt::Debug + ?Sized>(value: &T) -> Inspect<'_, T> {
+    Inspect {
+        pretty: true,
+        ..inspect(value)
+    }
+}
+
+impl<T: ?Sized> Inspect<'_, T> {
+    /// How many levels of nesting below the value itself are shown.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.limits.depth = depth;
+        self
+    }
+
+    pub fn max_items(mut self, max_items: usize) -> Self {
+        self.limits.max_items = max_items;
+        self
+    }
+
+    pub fn max_string(mut self, max_string: usize) -> Self {
+        self.limits.max_string = max_string;
+        self
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Display for Inspect<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut limiter = Limiter::new(f, self.pretty, self.limits);
+        if self.pretty {
+            write!(limiter, "{:#?}", self.value)
+        } else {
+            write!(limiter, "{:?}", self.value)
+        }
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for Inspect<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+struct Frame {
+    // Lists, maps and sets are cut at `max_items`, structs and tuples aren't
+    counted: bool,
+    items: usize,
+    // Reached `max_items`, anything but the closing bracket truncates it
+    full: bool,
+    truncated: bool,
+}
+
+// Cuts `Debug` output down as it streams through, tracking brackets and
+// string literals so it never has to hold the whole value in memory
+struct Limiter<'a, W> {
+    out: &'a mut W,
+    pretty: bool,
+    limits: Limits,
+    frames: Vec<Frame>,
+    // Output is dropped until the frame at this depth closes
+    hidden_until: Option<usize>,
+    // The quote of the string or char literal being written
+    quote: Option<char>,
+    escaped: bool,
+    string_len: usize,
+    last: char,
+}
+
+impl<'a, W: Write> Limiter<'a, W> {
+    fn new(out: &'a mut W, pretty: bool, limits: Limits) -> Self {
+        Limiter {
+            out,
+            pretty,
+            limits,
+            frames: vec![],
+            hidden_until: None,
+            quote: None,
+            escaped: false,
+            string_len: 0,
+            last: ' ',
+        }
+    }
+
+    fn push_literal(&mut self, c: char) -> fmt::Result {
+        let Some(quote) = self.quote else {
+            return Ok(());
+        };
+
+        if self.escaped {
+            self.escaped = false;
+        } else if c == quote {
+            self.quote = None;
+            if quote == '"' && self.string_len > self.limits.max_string {
+                self.out.write_char('…')?;
+            }
+            return self.out.write_char(c);
+        } else {
+            self.escaped = c == '\\';
+            self.string_len += 1;
+        }
+
+        if quote == '\'' || self.string_len <= self.limits.max_string {
+            self.out.write_char(c)?;
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self, c: char) -> fmt::Result {
+        let depth = self.frames.len();
+        let Some(frame) = self.frames.pop() else {
+            return self.out.write_char(c);
+        };
+
+        match self.hidden_until {
+            Some(until) if until == depth => self.hidden_until = None,
+            Some(_) => return Ok(()),
+            None => {}
+        }
+
+        if frame.truncated {
+            self.out.write_char('…')?;
+            if self.pretty {
+                self.out.write_char('\n')?;
+                for _ in 0..self.frames.len() {
+                    self.out.write_str("    ")?;
+                }
+            }
+        }
+
+        self.out.write_char(c)
+    }
+}
+
+impl<W: Write> Write for Limiter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.quote.is_some() {
+                if self.hidden_until.is_none() {
+                    self.push_literal(c)?;
+                } else if self.escaped {
+                    self.escaped = false;
+                } else if Some(c) == self.quote {
+                    self.quote = None;
+                } else {
+                    self.escaped = c == '\\';
+                }
+                continue;
+            }
+
+            if matches!(c, ']' | '}' | ')') {
+                self.close(c)?;
+                self.last = c;
+                continue;
+            }
+
+            if self.hidden_until.is_none()
+                && !c.is_whitespace()
+                && let Some(frame) = self.frames.last_mut()
+                && frame.full
+            {
+                frame.truncated = true;
+                self.hidden_until = Some(self.frames.len());
+            }
+
+            match c {
+                '"' | '\'' => {
+                    self.quote = Some(c);
+                    self.string_len = 0;
+                    if self.hidden_until.is_none() {
+                        self.out.write_char(c)?;
+                    }
+                }
+                '[' | '{' | '(' => {
+                    let named = self.last.is_alphanumeric() || self.last == '_';
+                    self.frames.push(Frame {
+                        counted: c == '[' || (c == '{' && !named),
+                        items: 0,
+                        full: false,
+                        truncated: false,
+                    });
+
+                    if self.hidden_until.is_none() {
+                        self.out.write_char(c)?;
+                        if self.frames.len() > self.limits.depth + 1 {
+                            self.out.write_char('…')?;
+                            self.hidden_until = Some(self.frames.len());
+                        }
+                    }
+                }
+                ',' if self.hidden_until.is_none() => {
+                    if let Some(frame) = self.frames.last_mut()
+                        && frame.counted
+                    {
+                        frame.items += 1;
+                        frame.full = frame.items >= self.limits.max_items;
+                    }
+                    self.out.write_char(c)?;
+                }
+                c if self.hidden_until.is_none() => self.out.write_char(c)?,
+                _ => {}
+            }
+
+            if !c.is_whitespace() {
+                self.last = c;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct User {
+        name: &'static str,
+        tags: Vec<&'static str>,
+    }
+
+    fn limits(depth: usize, max_items: usize, max_string: usize) -> Limits {
+        Limits {
+            depth,
+            max_items,
+            max_string,
+        }
+    }
+
+    #[test]
+    fn limits_depth_items_and_strings() {
+        let user = User {
+            name: "Ada Lovelace",
+            tags: vec!["math", "engines", "poetry"],
+        };
+
+        let inspect = |limits| {
+            Inspect {
+                value: &user,
+                pretty: false,
+                limits,
+            }
+            .to_string()
+        };
+
+        assert_eq!(
+            inspect(limits(2, 100, 100)),
+            "User { name: \"Ada Lovelace\", tags: [\"math\", \"engines\", \"poetry\"] }"
+        );
+        assert_eq!(
+            inspect(limits(0, 100, 100)),
+            "User { name: \"Ada Lovelace\", tags: […] }"
+        );
+        assert_eq!(
+            inspect(limits(2, 2, 4)),
+            "User { name: \"Ada …\", tags: [\"math\", \"engi…\", …] }"
+        );
+    }
+
+    #[test]
+    fn limits_pretty_output() {
+        let map = BTreeMap::from([("a", [1, 2, 3]), ("b", [4, 5, 6])]);
+        let pretty = |limits| {
+            Inspect {
+                value: &map,
+                pretty: true,
+                limits,
+            }
+            .to_string()
+        };
+
+        assert_eq!(pretty(limits(0, 1, 100)), "{\n    \"a\": […],\n    …\n}");
+        assert_eq!(
+            pretty(limits(2, 2, 100)),
+            "{\n    \"a\": [\n        1,\n        2,\n        …\n    ],\n    \"b\": [\n        4,\n        5,\n        …\n    ],\n}"
+        );
+    }
+
+    #[test]
+    fn brackets_and_quotes_inside_literals_are_text() {
+        let value = ("[{\"(", '"', '[', vec![1]);
+
+        assert_eq!(
+            inspect(&value).depth(0).to_string(),
+            "(\"[{\\\"(\", '\"', '[', […])"
+        );
+        assert_eq!(inspect(&vec![1, 2]).max_items(2).to_string(), "[1, 2]");
+        assert_eq!(inspect(&[1, 2, 3]).max_items(2).to_string(), "[1, 2, …]");
+    }
+
+    #[test]
+    fn reads_limits_from_env() {
+        let limits = limits_from(|name| match name {
+            "DEBUG_DEPTH" => Some("5".into()),
+            "DEBUG_MAX_ARRAY_LENGTH" => Some("nope".into()),
+            _ => None,
+        });
+
+        assert_eq!(
+            (limits.depth, limits.max_items, limits.max_string),
+            (5, 100, 10_000)
+        );
+    }
+}
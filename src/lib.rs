@@ -1,199 +1,792 @@
-use core::fmt::{self, write};
+use color::{assign_color, release_color};
+use core::fmt::write;
+use format::Record;
 use std::{
-    cell::Cell,
+    collections::BTreeMap,
     fmt::Arguments,
-    hash::{DefaultHasher, Hash, Hasher},
-    time::Instant,
+    panic,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
-const COLORS: [&str; 76] = [
-    "#0000CC", "#0000FF", "#0033CC", "#0033FF", "#0066CC", "#0066FF", "#0099CC", "#0099FF",
-    "#00CC00", "#00CC33", "#00CC66", "#00CC99", "#00CCCC", "#00CCFF", "#3300CC", "#3300FF",
-    "#3333CC", "#3333FF", "#3366CC", "#3366FF", "#3399CC", "#3399FF", "#33CC00", "#33CC33",
-    "#33CC66", "#33CC99", "#33CCCC", "#33CCFF", "#6600CC", "#6600FF", "#6633CC", "#6633FF",
-    "#66CC00", "#66CC33", "#9900CC", "#9900FF", "#9933CC", "#9933FF", "#99CC00", "#99CC33",
-    "#CC0000", "#CC0033", "#CC0066", "#CC0099", "#CC00CC", "#CC00FF", "#CC3300", "#CC3333",
-    "#CC3366", "#CC3399", "#CC33CC", "#CC33FF", "#CC6600", "#CC6633", "#CC9900", "#CC9933",
-    "#CCCC00", "#CCCC33", "#FF0000", "#FF0033", "#FF0066", "#FF0099", "#FF00CC", "#FF00FF",
-    "#FF3300", "#FF3333", "#FF3366", "#FF3399", "#FF33CC", "#FF33FF", "#FF6600", "#FF6633",
-    "#FF9900", "#FF9933", "#FFCC00", "#FFCC33",
-];
-
-fn xterm_color_index_for_string(input: &str) -> u8 {
-    let mut hasher = DefaultHasher::new();
-    input.hash(&mut hasher);
-    let hex_index = (hasher.finish() % COLORS.len() as u64) as usize;
-    let hex = COLORS[hex_index];
-    let ansi_color = ansi_256_from_hex(hex);
+mod async_sink;
+mod color;
+mod duration;
+mod field;
+mod filter;
+mod format;
+mod hyperlink;
+mod inspect;
+mod level;
+#[cfg(feature = "log")]
+pub mod log_backend;
+#[cfg(any(feature = "log", feature = "tracing"))]
+mod loggers;
+mod rotating;
+mod routing;
+mod sink;
+mod template;
+mod timestamp;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+
+pub use async_sink::{AsyncSink, AsyncSinkBuilder, FlushGuard, Overflow};
+pub use color::{
+    ColorAssignment, ColorDepth, Rgb, Theme, set_color_assignment, set_palette, set_theme,
+};
+pub use duration::{DiffMode, DiffPrecision, humanize};
+pub use field::{Field, Value};
+pub use filter::{disable, enable, enabled};
+pub use format::{Format, Location, LocationStyle, Multiline};
+pub use inspect::{Inspect, inspect, inspect_pretty};
+pub use level::{Level, ParseLevelError};
+
+pub use template::{Template, TemplateError};
+pub use timestamp::{TimePrecision, Timestamp};
+
+pub use rotating::{Retention, RotatingFileSink, RotatingFileSinkBuilder, Rotation};
+pub use routing::{Route, Router, set_router};
+pub use sink::{
+    FileSink, MemorySink, Sink, StderrSink, StdoutSink, colors_enabled, default_sink,
+    set_default_sink,
+};
 
-    ansi_color.unwrap_or(123)
+// When the OS started the process, or where it can't say, the first time
+// dbug needed a clock. Diffs are stored relative to it and the uptime column
+// counts from it.
+fn process_start() -> Instant {
+    static START: OnceLock<Instant> = OnceLock::new();
+
+    *START.get_or_init(|| {
+        let now = Instant::now();
+        timestamp::process_age()
+            .and_then(|age| now.checked_sub(age))
+            .unwrap_or(now)
+    })
 }
 
-fn colorize(color: u8, prefix: &str) -> String {
-    format!("\x1b[1;38;5;{}m{}\x1b[0m", color, prefix)
-}
+// Stored as nanoseconds since process start plus one, so zero means "never logged"
+struct LastLog(AtomicU64);
 
-fn ansi_256_from_hex(hex: &str) -> Result<u8, Box<dyn std::error::Error>> {
-    let hex = hex.trim_start_matches('#');
+impl LastLog {
+    fn new() -> Self {
+        process_start();
 
-    let r = u8::from_str_radix(&hex[0..2], 16)?;
-    let g = u8::from_str_radix(&hex[2..4], 16)?;
-    let b = u8::from_str_radix(&hex[4..6], 16)?;
+        LastLog::unset()
+    }
 
-    Ok(rgb_to_ansi256(r, g, b))
+    const fn unset() -> Self {
+        LastLog(AtomicU64::new(0))
+    }
+
+    fn swap(&self, now: Instant) -> Option<Duration> {
+        let nanos = now.saturating_duration_since(process_start()).as_nanos() as u64 + 1;
+        let previous = self.0.swap(nanos, Ordering::AcqRel);
+
+        (previous != 0).then(|| Duration::from_nanos(nanos.saturating_sub(previous)))
+    }
 }
 
-fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
-    // Grayscale approximation
-    if r == g && g == b {
-        if r < 8 {
-            return 16;
-        }
-        if r > 248 {
-            return 231;
-        }
-        return 232 + ((r as u16 - 8) * 24 / 247) as u8;
+// The clock of every logger in `DiffMode::Global`
+static GLOBAL_LAST_LOG: LastLog = LastLog::unset();
+
+// The clocks of `DiffMode::Subtree`, one per namespace, each ticked by lines
+// of that namespace and of every namespace below it
+static SUBTREE_LAST_LOGS: Mutex<BTreeMap<String, LastLog>> = Mutex::new(BTreeMap::new());
+
+fn subtree_swap(namespace: &str, now: Instant) -> Option<Duration> {
+    let mut clocks = SUBTREE_LAST_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut swap = |namespace: &str| match clocks.get(namespace) {
+        Some(clock) => clock.swap(now),
+        None => clocks
+            .entry(namespace.to_string())
+            .or_insert_with(LastLog::unset)
+            .swap(now),
+    };
+
+    let elapsed = swap(namespace);
+    let mut namespace = namespace;
+    while let Some((parent, _)) = namespace.rsplit_once(':') {
+        swap(parent);
+        namespace = parent;
     }
 
-    // RGB 6x6x6 Cube (16–231)
-    let r = scale_to_ansi(r);
-    let g = scale_to_ansi(g);
-    let b = scale_to_ansi(b);
+    elapsed
+}
 
-    16 + 36 * r + 6 * g + b
+pub struct Logger {
+    raw_label: String,
+    // generation << 3 | threshold, refreshed whenever the global filter changes
+    threshold: AtomicU64,
+    color: Rgb,
+    // Whether `color` is registered with the distinct color allocator
+    color_assigned: bool,
+    last_log: LastLog,
+    sink: Option<Arc<dyn Sink>>,
+    format: Option<Format>,
+    precision: Option<DiffPrecision>,
+    multiline: Option<Multiline>,
+    location_style: Option<LocationStyle>,
+    timestamp: Option<Timestamp>,
+    time_precision: Option<TimePrecision>,
+    uptime: Option<bool>,
+    diff_mode: Option<DiffMode>,
 }
 
-fn scale_to_ansi(value: u8) -> u8 {
-    match value {
-        0..=47 => 0,
-        48..=114 => 1,
-        115..=154 => 2,
-        155..=194 => 3,
-        195..=234 => 4,
-        _ => 5,
-    }
+pub struct LoggerBuilder {
+    label: String,
+    sink: Option<Arc<dyn Sink>>,
+    format: Option<Format>,
+    precision: Option<DiffPrecision>,
+    multiline: Option<Multiline>,
+    location_style: Option<LocationStyle>,
+    timestamp: Option<Timestamp>,
+    time_precision: Option<TimePrecision>,
+    uptime: Option<bool>,
+    diff_mode: Option<DiffMode>,
+    color: Option<Rgb>,
 }
 
-fn parse_filter() -> Vec<String> {
-    match std::env::var("DEBUG") {
-        Ok(debug) if debug.contains(" ") => debug
-            .split(" ")
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .map(String::from)
-            .collect(),
+impl LoggerBuilder {
+    pub fn sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
 
-        Ok(debug) if debug.contains(",") => debug
-            .split(",")
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
-            .map(String::from)
-            .collect(),
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
 
-        Ok(debug) => vec![debug],
+    pub fn diff_precision(mut self, precision: DiffPrecision) -> Self {
+        self.precision = Some(precision);
+        self
+    }
 
-        Err(_) => vec![],
+    /// How messages with line breaks are laid out, `Multiline::Prefix` by default.
+    pub fn multiline(mut self, multiline: Multiline) -> Self {
+        self.multiline = Some(multiline);
+        self
     }
-}
 
-pub struct Logger {
-    raw_label: String,
-    label: String,
-    filter: Vec<String>,
-    color: u8,
-    last_log: Cell<Option<Instant>>,
+    /// Shows where each line was logged, `LocationStyle::Hidden` by default.
+    pub fn location(mut self, style: LocationStyle) -> Self {
+        self.location_style = Some(style);
+        self
+    }
+
+    /// Starts lines with the wall-clock time, `Timestamp::Off` by default.
+    pub fn timestamp(mut self, timestamp: Timestamp) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Milliseconds or microseconds in timestamps and the uptime column.
+    pub fn time_precision(mut self, precision: TimePrecision) -> Self {
+        self.time_precision = Some(precision);
+        self
+    }
+
+    /// Adds a column with the time since the process started, which is the
+    /// same clock for every logger.
+    pub fn uptime(mut self, uptime: bool) -> Self {
+        self.uptime = Some(uptime);
+        self
+    }
+
+    /// Which earlier line `+diff`s are measured from, `DiffMode::Namespace` by default.
+    pub fn diff_mode(mut self, mode: DiffMode) -> Self {
+        self.diff_mode = Some(mode);
+        self
+    }
+
+    /// Uses `color` for this namespace instead of picking one from the palette.
+    pub fn color(mut self, color: Rgb) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn build(self) -> Logger {
+        let raw_label = self.label;
+        let (color, color_assigned) = assign_color(&raw_label, self.color);
+
+        Logger {
+            color,
+            color_assigned,
+            raw_label,
+            threshold: AtomicU64::new(0),
+            last_log: LastLog::new(),
+            sink: self.sink,
+            format: self.format,
+            precision: self.precision,
+            multiline: self.multiline,
+            location_style: self.location_style,
+            timestamp: self.timestamp,
+            time_precision: self.time_precision,
+            uptime: self.uptime,
+            diff_mode: self.diff_mode,
+        }
+    }
 }
 
 #[macro_export]
 macro_rules! dbug {
+    ($logger:expr, $fmt:literal $(, $arg:expr)* ; $($fields:tt)+) => {
+        match &$logger {
+            logger => {
+                if logger.enabled() {
+                    logger.log_from(
+                        None,
+                        format_args!($fmt $(, $arg)*),
+                        &$crate::__dbug_fields!($($fields)+),
+                        Some($crate::__dbug_location!()),
+                    )
+                }
+            }
+        }
+    };
     ($logger:expr, $($arg:tt)*) => {
-        $logger.log_fmt(format_args!($($arg)*))
-    }
+        match &$logger {
+            logger => {
+                if logger.enabled() {
+                    logger.log_from(
+                        None,
+                        format_args!($($arg)*),
+                        &[],
+                        Some($crate::__dbug_location!()),
+                    )
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __dbug_at {
+    ($level:expr, $logger:expr, $fmt:literal $(, $arg:expr)* ; $($fields:tt)+) => {
+        match &$logger {
+            logger => {
+                if logger.enabled_at($level) {
+                    logger.log_from(
+                        Some($level),
+                        format_args!($fmt $(, $arg)*),
+                        &$crate::__dbug_fields!($($fields)+),
+                        Some($crate::__dbug_location!()),
+                    )
+                }
+            }
+        }
+    };
+    ($level:expr, $logger:expr, $($arg:tt)*) => {
+        match &$logger {
+            logger => {
+                if logger.enabled_at($level) {
+                    logger.log_from(
+                        Some($level),
+                        format_args!($($arg)*),
+                        &[],
+                        Some($crate::__dbug_location!()),
+                    )
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __dbug_location {
+    () => {
+        $crate::Location {
+            file: file!(),
+            line: line!(),
+            module: Some(module_path!()),
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! dbug_trace {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::__dbug_at!($crate::Level::Trace, $logger, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! dbug_debug {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::__dbug_at!($crate::Level::Debug, $logger, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! dbug_info {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::__dbug_at!($crate::Level::Info, $logger, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! dbug_warn {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::__dbug_at!($crate::Level::Warn, $logger, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! dbug_error {
+    ($logger:expr, $($arg:tt)*) => {
+        $crate::__dbug_at!($crate::Level::Error, $logger, $($arg)*)
+    };
 }
 
 impl Logger {
     pub fn new(label: &str) -> Self {
-        let raw_label = label.to_string();
-        let color = xterm_color_index_for_string(&raw_label);
-        let label = colorize(color, &raw_label);
-        let filter = parse_filter();
+        Logger::builder(label).build()
+    }
 
-        Logger {
-            color,
-            raw_label,
-            label,
-            filter,
-            last_log: None.into(),
+    pub fn builder(label: &str) -> LoggerBuilder {
+        LoggerBuilder {
+            label: label.to_string(),
+            sink: None,
+            format: None,
+            precision: None,
+            multiline: None,
+            location_style: None,
+            timestamp: None,
+            time_precision: None,
+            uptime: None,
+            diff_mode: None,
+            color: None,
         }
     }
 
+    pub fn enabled(&self) -> bool {
+        self.enabled_at(Level::Debug)
+    }
+
+    pub fn enabled_at(&self, level: Level) -> bool {
+        self.threshold().is_some_and(|threshold| level >= threshold)
+    }
+
+    #[track_caller]
     pub fn log_fmt(&self, args: Arguments) {
+        self.log_fields(None, args, &[]);
+    }
+
+    #[track_caller]
+    pub fn log(&self, message: &str) {
+        if !self.enabled() {
+            return;
+        }
+
+        self.write(None, message, &[], Some(panic::Location::caller().into()));
+    }
+
+    #[track_caller]
+    pub fn log_fmt_at(&self, level: Level, args: Arguments) {
+        self.log_fields(Some(level), args, &[]);
+    }
+
+    // A `None` level logs like `log`, without a level in the line
+    #[track_caller]
+    pub fn log_fields(&self, level: Option<Level>, args: Arguments, fields: &[Field]) {
+        self.log_from(level, args, fields, Some(panic::Location::caller().into()));
+    }
+
+    // For the macros and records from other logging libraries, which know
+    // their own location
+    #[doc(hidden)]
+    pub fn log_from(
+        &self,
+        level: Option<Level>,
+        args: Arguments,
+        fields: &[Field],
+        location: Option<Location>,
+    ) {
+        if !self.enabled_at(level.unwrap_or(Level::Debug)) {
+            return;
+        }
+
         let mut msg = String::new();
         let _ = write(&mut msg, args);
 
-        self.log(&msg);
+        self.write(level, &msg, fields, location);
     }
 
-    pub fn log(&self, message: &str) {
-        if !self.should_log() {
+    #[track_caller]
+    pub fn log_at(&self, level: Level, message: &str) {
+        if !self.enabled_at(level) {
             return;
         }
 
-        let ms_diff = if let Some(last) = self.last_log.get() {
-            let time = Instant::now();
-            let elapsed = (time - last).as_millis();
+        self.write(
+            Some(level),
+            message,
+            &[],
+            Some(panic::Location::caller().into()),
+        );
+    }
+
+    #[track_caller]
+    pub fn trace(&self, message: &str) {
+        self.log_at(Level::Trace, message);
+    }
 
-            colorize(self.color, &format!("+{}", elapsed))
-        } else {
-            colorize(self.color, "+0")
+    #[track_caller]
+    pub fn debug(&self, message: &str) {
+        self.log_at(Level::Debug, message);
+    }
+
+    #[track_caller]
+    pub fn info(&self, message: &str) {
+        self.log_at(Level::Info, message);
+    }
+
+    #[track_caller]
+    pub fn warn(&self, message: &str) {
+        self.log_at(Level::Warn, message);
+    }
+
+    #[track_caller]
+    pub fn error(&self, message: &str) {
+        self.log_at(Level::Error, message);
+    }
+
+    fn write(
+        &self,
+        level: Option<Level>,
+        message: &str,
+        fields: &[Field],
+        location: Option<Location>,
+    ) {
+        let now = Instant::now();
+        let elapsed = match self.diff_mode() {
+            DiffMode::Namespace => self.last_log.swap(now),
+            DiffMode::Global => GLOBAL_LAST_LOG.swap(now),
+            DiffMode::Subtree => subtree_swap(&self.raw_label, now),
+        };
+        let mut record = Record::new(&self.raw_label, level, message, fields);
+        record.location = location;
+        record.elapsed = elapsed;
+        record.uptime = now.saturating_duration_since(process_start());
+        record.color = self.color;
+        record.precision = self.precision.unwrap_or_else(DiffPrecision::from_env);
+        record.multiline = self.multiline.unwrap_or_else(Multiline::from_env);
+        record.location_style = self.location_style.unwrap_or_else(LocationStyle::from_env);
+        record.timestamp_style = self.timestamp.unwrap_or_else(Timestamp::from_env);
+        record.time_precision = self.time_precision.unwrap_or_else(TimePrecision::from_env);
+        record.show_uptime = self.uptime.unwrap_or_else(timestamp::uptime_from_env);
+        let format = self.format.as_ref().unwrap_or_else(|| Format::from_env());
+        let mut emit = |sink: &dyn Sink, format: &Format| {
+            let colors = sink.colors().then(|| sink.color_depth());
+            record.single_line = sink.single_line();
+            sink.write_line(&format.render(&record, colors));
         };
 
-        println!("{} {} {}", self.label, message, ms_diff);
+        if let Some(sink) = &self.sink {
+            return emit(sink.as_ref(), format);
+        }
 
-        self.last_log.set(Some(Instant::now()));
+        let router = routing::router();
+        match router.targets(&self.raw_label) {
+            Some(targets) => {
+                for target in targets {
+                    emit(
+                        target.sink.as_ref(),
+                        target.format.as_ref().unwrap_or(format),
+                    );
+                }
+            }
+            None => emit(default_sink().as_ref(), format),
+        }
     }
 
     pub fn extend(&self, extension: &str) -> Logger {
-        Logger::new(&format!("{}:{}", self.raw_label, extension))
+        let mut builder = Logger::builder(&format!("{}:{}", self.raw_label, extension));
+        builder.sink = self.sink.clone();
+        builder.format = self.format.clone();
+        builder.precision = self.precision;
+        builder.multiline = self.multiline;
+        builder.location_style = self.location_style;
+        builder.timestamp = self.timestamp;
+        builder.time_precision = self.time_precision;
+        builder.uptime = self.uptime;
+        builder.diff_mode = self.diff_mode;
+
+        builder.build()
     }
 
+    /// Closures can't be `#[track_caller]`, so their lines have no location.
     pub fn to_closure(&self) -> impl Fn(&str) {
         |message: &str| {
-            self.log(message);
+            if self.enabled() {
+                self.write(None, message, &[], None);
+            }
         }
     }
 
-    fn should_log(&self) -> bool {
-        // handle negations, -somelabel and -somelabel*
-        for filter in &self.filter {
-            if filter.starts_with("-") && !filter.ends_with("*") && filter[1..] == self.raw_label {
-                return false;
-            }
+    fn diff_mode(&self) -> DiffMode {
+        self.diff_mode.unwrap_or_else(DiffMode::from_env)
+    }
 
-            if filter.starts_with("-")
-                && filter.ends_with("*")
-                && self.raw_label.starts_with(&filter[1..filter.len() - 1])
-            {
-                return false;
-            }
+    fn threshold(&self) -> Option<Level> {
+        let generation = filter::generation();
+        let cached = self.threshold.load(Ordering::Relaxed);
+        if cached >> 3 == generation {
+            return (cached & 0b111)
+                .checked_sub(1)
+                .map(|i| Level::ALL[i as usize]);
         }
 
-        for filter in &self.filter {
-            if self.raw_label == *filter
-                || (filter.ends_with("*")
-                    && self.raw_label.starts_with(&filter[0..filter.len() - 1]))
-                || filter == "*"
-            {
-                return true;
-            }
-        }
+        let threshold = filter::threshold(&self.raw_label);
+        let code = threshold.map_or(0, |level| level as u64 + 1);
+        self.threshold
+            .store(generation << 3 | code, Ordering::Relaxed);
+
+        threshold
+    }
+}
 
-        false
+impl Drop for Logger {
+    fn drop(&mut self) {
+        if self.color_assigned {
+            release_color(&self.raw_label, self.color);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::fmt;
+
     #[test]
     fn it_works() {}
+
+    #[test]
+    fn logger_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Logger>();
+    }
+
+    #[test]
+    fn last_log_reports_elapsed_since_previous_swap() {
+        let last_log = LastLog::new();
+        let now = Instant::now();
+
+        assert_eq!(last_log.swap(now), None);
+        assert_eq!(
+            last_log.swap(now + Duration::from_millis(158)),
+            Some(Duration::from_millis(158))
+        );
+    }
+
+    #[test]
+    fn disabled_logger_skips_formatting() {
+        struct Expensive;
+
+        impl fmt::Debug for Expensive {
+            fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+                panic!("formatted while disabled");
+            }
+        }
+
+        let _guard = filter::lock_for_test();
+        enable("*,-disabled");
+
+        let sink = MemorySink::new();
+        let logger = Logger::builder("disabled").sink(sink.clone()).build();
+
+        assert!(!logger.enabled());
+        dbug!(logger, "{:?}", Expensive);
+        logger.log_fmt(format_args!("{:?}", Expensive));
+        assert!(sink.lines().is_empty());
+    }
+    #[test]
+    fn enable_updates_existing_loggers() {
+        let _guard = filter::lock_for_test();
+        enable("runtime");
+
+        let sink = MemorySink::new();
+        let logger = Logger::builder("runtime").sink(sink.clone()).build();
+        logger.log("before");
+
+        let previous = disable();
+        assert_eq!(previous, "runtime");
+        assert!(!logger.enabled());
+        logger.log("while disabled");
+
+        enable(&previous);
+        assert!(enabled("runtime"));
+        logger.log("after");
+
+        assert_eq!(sink.lines().len(), 2);
+    }
+    #[test]
+    fn levels_below_the_namespace_threshold_are_skipped() {
+        let _guard = filter::lock_for_test();
+        enable("leveled=warn");
+
+        let sink = MemorySink::new();
+        let logger = Logger::builder("leveled").sink(sink.clone()).build();
+
+        logger.log("plain");
+        logger.info("info");
+        dbug_trace!(logger, "trace {}", 1);
+        logger.warn("warn");
+        dbug_error!(logger, "error {}", 2);
+
+        let lines = sink.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" leveled WARN  warn"));
+        assert!(lines[1].ends_with(" leveled ERROR error 2"));
+    }
+
+    #[test]
+    fn macros_attach_fields() {
+        let _guard = filter::lock_for_test();
+        enable("fields");
+
+        let sink = MemorySink::new();
+        let logger = Logger::builder("fields").sink(sink.clone()).build();
+        let path = "/users";
+
+        dbug!(logger, "request done"; status = 200, path = %path);
+        dbug_warn!(logger, "slow {}", "query"; ms = 250u64);
+
+        let lines = sink.lines();
+        assert!(lines[0].ends_with(" fields request done status=200 path=/users"));
+        assert!(lines[1].ends_with(" fields WARN  slow query ms=250"));
+    }
+
+    #[test]
+    fn macros_evaluate_the_logger_once() {
+        let _guard = filter::lock_for_test();
+        enable("once");
+
+        let sink = MemorySink::new();
+        let built = std::cell::Cell::new(0);
+        let make_logger = || {
+            built.set(built.get() + 1);
+            Logger::builder("once").sink(sink.clone()).build()
+        };
+
+        dbug!(make_logger(), "plain");
+        dbug!(make_logger(), "with fields"; n = 1);
+        dbug_info!(make_logger(), "info");
+        dbug_warn!(make_logger(), "warn with fields"; n = 2);
+
+        assert_eq!(built.get(), 4);
+        assert_eq!(sink.lines().len(), 4);
+    }
+
+    #[test]
+    fn diff_modes_choose_the_previous_line() {
+        let _guard = filter::lock_for_test();
+        enable("diff*");
+
+        let sink = MemorySink::new();
+        let logger = |name: &str, mode| {
+            Logger::builder(name)
+                .sink(sink.clone())
+                .format(Format::Template(Template::parse("{ns} {diff}").unwrap()))
+                .diff_precision(DiffPrecision::Nanos)
+                .diff_mode(mode)
+                .build()
+        };
+        // With nanosecond precision, only lines without an earlier one show +0ms
+        let first_line = |line: &str| line.ends_with(" +0ms");
+
+        let namespace = logger("diff:namespace", DiffMode::Namespace);
+        namespace.log("parent");
+        namespace.extend("child").log("child");
+
+        let subtree = logger("diff:subtree", DiffMode::Subtree);
+        subtree.log("parent");
+        subtree.extend("child").log("child");
+        logger("diff:subtree", DiffMode::Subtree).log("same namespace");
+        logger("diff:subtree:sibling", DiffMode::Subtree).log("sibling");
+
+        logger("diff:global:a", DiffMode::Global).log("a");
+        logger("diff:global:b", DiffMode::Global).log("b");
+
+        let lines = sink.lines();
+        let first_lines: Vec<bool> = lines.iter().map(|line| first_line(line)).collect();
+        assert_eq!(
+            first_lines,
+            [true, true, true, true, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn routes_send_namespaces_to_their_sinks() {
+        let _guard = filter::lock_for_test();
+        enable("routing:*");
+
+        let db = MemorySink::new();
+        let everything = MemorySink::new();
+        routing::set_router(
+            Router::new()
+                .route(
+                    Route::new("routing:db*")
+                        .sink(db.clone())
+                        .sink_with_format(everything.clone(), Format::Json),
+                )
+                .route(Route::new("routing:*").sink(everything.clone())),
+        );
+
+        Logger::new("routing:db:read").log("query");
+        Logger::new("routing:http").log("request");
+        let own = MemorySink::new();
+        Logger::builder("routing:db:own")
+            .sink(own.clone())
+            .build()
+            .log("own sink");
+        routing::set_router(Router::new());
+
+        assert_eq!(db.lines().len(), 1);
+        assert!(db.lines()[0].ends_with(" routing:db:read query"));
+        let everything = everything.lines();
+        assert!(everything[0].starts_with("{\"namespace\":\"routing:db:read\""));
+        assert!(everything[1].ends_with(" routing:http request"));
+        assert_eq!(everything.len(), 2);
+        assert_eq!(own.lines().len(), 1);
+    }
+
+    #[test]
+    fn macros_and_methods_record_where_they_were_called() {
+        let _guard = filter::lock_for_test();
+        enable("location");
+
+        let sink = MemorySink::new();
+        let logger = Logger::builder("location")
+            .sink(sink.clone())
+            .location(LocationStyle::Plain)
+            .build();
+        let json = Logger::builder("location")
+            .sink(sink.clone())
+            .format(Format::Json)
+            .build();
+
+        let line = line!() + 1;
+        dbug!(logger, "from the macro");
+        logger.log("from the method");
+        dbug_info!(json, "as json");
+        logger.to_closure()("from a closure");
+
+        let lines = sink.lines();
+        assert!(lines[0].ends_with(&format!(" from the macro src/lib.rs:{}", line)));
+        assert!(lines[1].ends_with(&format!(" from the method src/lib.rs:{}", line + 1)));
+        assert!(lines[2].ends_with(&format!(
+            ",\"file\":\"src/lib.rs\",\"line\":{},\"module\":\"dbug::tests\"}}",
+            line + 2
+        )));
+        assert!(lines[3].ends_with(" location from a closure"));
+    }
 }
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use log::{LevelFilter, Metadata, Record, SetLoggerError};
+
+use crate::{Level, Location, Sink, filter, loggers::Loggers};
+
+fn level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::Error,
+        log::Level::Warn => Level::Warn,
+        log::Level::Info => Level::Info,
+        log::Level::Debug => Level::Debug,
+        log::Level::Trace => Level::Trace,
+    }
+}
+
+// `hyper::proto::h1` becomes the `hyper:proto:h1` namespace
+fn namespace(target: &str) -> String {
+    target.replace("::", ":")
+}
+
+/// A `log::Log` implementation that prints records through a dbug `Logger`
+/// per target, so the `DEBUG` filter, colors and `+ms` diffs all apply.
+pub struct DbugLog {
+    loggers: Loggers,
+}
+
+impl DbugLog {
+    pub fn new() -> Self {
+        DbugLog {
+            loggers: Loggers::new(None),
+        }
+    }
+
+    pub fn with_sink(sink: impl Sink + 'static) -> Self {
+        DbugLog {
+            loggers: Loggers::new(Some(Arc::new(sink))),
+        }
+    }
+}
+
+impl Default for DbugLog {
+    fn default() -> Self {
+        DbugLog::new()
+    }
+}
+
+impl log::Log for DbugLog {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        filter::threshold(&namespace(metadata.target()))
+            .is_some_and(|threshold| level(metadata.level()) >= threshold)
+    }
+
+    fn log(&self, record: &Record) {
+        // The `log` macros don't ask `enabled` first, and a logger built for a
+        // disabled target would stay cached, holding its color
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let logger = self.loggers.get(&namespace(record.target()));
+        let location = record
+            .file_static()
+            .zip(record.line())
+            .map(|(file, line)| Location {
+                file,
+                line,
+                module: record.module_path_static(),
+            });
+
+        logger.log_from(Some(level(record.level())), *record.args(), &[], location);
+    }
+
+    fn flush(&self) {
+        self.loggers.flush();
+    }
+}
+
+/// Installs a `DbugLog` as the global `log` logger.
+pub fn init() -> Result<(), SetLoggerError> {
+    log::set_boxed_logger(Box::new(DbugLog::new()))?;
+    log::set_max_level(LevelFilter::Trace);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Format, MemorySink, Route, Router, enable, routing};
+    use log::Log;
+
+    fn log(logger: &DbugLog, target: &str, level: log::Level, message: &str) {
+        logger.log(
+            &Record::builder()
+                .target(target)
+                .level(level)
+                .args(format_args!("{}", message))
+                .file_static(Some("src/db/pool.rs"))
+                .line(Some(42))
+                .module_path_static(Some("app::db::pool"))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn records_are_filtered_by_target_and_level() {
+        let _guard = filter::lock_for_test();
+        enable("log_backend:db=info,-log_backend:db:noisy");
+
+        let sink = MemorySink::new();
+        let logger = DbugLog::with_sink(sink.clone());
+
+        log(&logger, "log_backend::db", log::Level::Warn, "slow query");
+        log(
+            &logger,
+            "log_backend::db",
+            log::Level::Debug,
+            "below the threshold",
+        );
+        log(
+            &logger,
+            "log_backend::db::noisy",
+            log::Level::Error,
+            "disabled",
+        );
+        log(
+            &logger,
+            "log_backend::http",
+            log::Level::Error,
+            "not enabled",
+        );
+
+        let lines = sink.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with(" log_backend:db WARN  slow query"));
+        assert_eq!(logger.loggers.len(), 1);
+        assert!(
+            logger.enabled(
+                &Metadata::builder()
+                    .target("log_backend::db")
+                    .level(log::Level::Info)
+                    .build()
+            )
+        );
+        assert!(
+            !logger.enabled(
+                &Metadata::builder()
+                    .target("log_backend::db")
+                    .level(log::Level::Debug)
+                    .build()
+            )
+        );
+    }
+
+    #[test]
+    fn records_keep_their_location_and_module() {
+        let _guard = filter::lock_for_test();
+        enable("log_backend:located");
+
+        let sink = MemorySink::new();
+        routing::set_router(
+            Router::new().route(
+                Route::new("log_backend:located").sink_with_format(sink.clone(), Format::Json),
+            ),
+        );
+        log(
+            &DbugLog::new(),
+            "log_backend::located",
+            log::Level::Info,
+            "query",
+        );
+        routing::set_router(Router::new());
+
+        let lines = sink.lines();
+        assert!(lines[0].starts_with(
+            "{\"namespace\":\"log_backend:located\",\"level\":\"info\",\"message\":\"query\""
+        ));
+        assert!(
+            lines[0].ends_with(
+                ",\"file\":\"src/db/pool.rs\",\"line\":42,\"module\":\"app::db::pool\"}"
+            )
+        );
+    }
+
+    #[test]
+    fn targets_become_namespaces() {
+        assert_eq!(namespace("hyper::proto::h1"), "hyper:proto:h1");
+    }
+
+    #[test]
+    fn maps_log_levels() {
+        assert_eq!(level(log::Level::Warn), Level::Warn);
+        assert_eq!(level(log::Level::Trace), Level::Trace);
+    }
+}
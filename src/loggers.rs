@@ -0,0 +1,56 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{Logger, Sink, default_sink, routing};
+
+// Loggers made on demand for namespaces coming from other logging libraries,
+// kept around so each namespace keeps its own `+ms` diff
+pub(crate) struct Loggers {
+    sink: Option<Arc<dyn Sink>>,
+    loggers: RwLock<HashMap<String, Arc<Logger>>>,
+}
+
+impl Loggers {
+    pub(crate) fn new(sink: Option<Arc<dyn Sink>>) -> Self {
+        Loggers {
+            sink,
+            loggers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn get(&self, namespace: &str) -> Arc<Logger> {
+        if let Ok(loggers) = self.loggers.read()
+            && let Some(logger) = loggers.get(namespace)
+        {
+            return logger.clone();
+        }
+
+        let mut loggers = self.loggers.write().unwrap_or_else(|e| e.into_inner());
+        loggers
+            .entry(namespace.to_string())
+            .or_insert_with(|| {
+                let mut builder = Logger::builder(namespace);
+                builder.sink = self.sink.clone();
+
+                Arc::new(builder.build())
+            })
+            .clone()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.loggers.read().map_or(0, |loggers| loggers.len())
+    }
+
+    pub(crate) fn flush(&self) {
+        match &self.sink {
+            Some(sink) => sink.flush(),
+            None => {
+                default_sink().flush();
+                routing::flush();
+            }
+        }
+    }
+}
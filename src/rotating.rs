@@ -0,0 +1,638 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    Sink,
+    timestamp::{self, TimePrecision, Timestamp},
+};
+
+/// When a `RotatingFileSink` moves its file aside and starts a new one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Never,
+    /// Before a line would take the file past this many bytes
+    Size(u64),
+    /// At the start of every UTC hour
+    Hourly,
+    /// At UTC midnight
+    Daily,
+}
+
+impl Rotation {
+    fn period(&self) -> Option<u64> {
+        match self {
+            Rotation::Hourly => Some(3600),
+            Rotation::Daily => Some(86_400),
+            Rotation::Never | Rotation::Size(_) => None,
+        }
+    }
+}
+
+/// Which rotated files a `RotatingFileSink` keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retention {
+    /// The newest this many rotated files
+    Files(usize),
+    /// Rotated files modified in the last this many days
+    Days(u64),
+}
+
+pub struct RotatingFileSinkBuilder {
+    path: PathBuf,
+    rotation: Rotation,
+    retention: Option<Retention>,
+    compress: bool,
+    colors: bool,
+}
+
+impl RotatingFileSinkBuilder {
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Deletes older rotated files after each rotation. Everything is kept by default.
+    pub fn retention(mut self, retention: Retention) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Gzips rotated files, adding `.gz` to their names.
+    #[cfg(feature = "gzip")]
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Keeps ANSI colors in the file, which are left out by default.
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    pub fn build(self) -> io::Result<RotatingFileSink> {
+        let file = open(&self.path)?;
+        let metadata = file.metadata()?;
+        // A file left over from an earlier run belongs to the period it was last written in
+        let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+
+        Ok(RotatingFileSink {
+            active: Mutex::new(Active {
+                file: Some(file),
+                size: metadata.len(),
+                period: self
+                    .rotation
+                    .period()
+                    .map_or(0, |period| secs(modified) / period),
+            }),
+            path: self.path,
+            rotation: self.rotation,
+            retention: self.retention,
+            compress: self.compress,
+            colors: self.colors,
+        })
+    }
+}
+
+struct Active {
+    // Only `None` while the file is being renamed
+    file: Option<File>,
+    size: u64,
+    period: u64,
+}
+
+/// Appends lines to a file and rotates it by size or time, so long runs can
+/// log to disk without filling it. Rotated files are named after the time
+/// they start (time rotation) or end (size rotation), like `app.log.2026-10-17`.
+///
+/// Every record is exactly one line: line breaks in messages are written as `\n`.
+pub struct RotatingFileSink {
+    path: PathBuf,
+    rotation: Rotation,
+    retention: Option<Retention>,
+    compress: bool,
+    colors: bool,
+    active: Mutex<Active>,
+}
+
+impl RotatingFileSink {
+    pub fn builder(path: impl AsRef<Path>) -> RotatingFileSinkBuilder {
+        RotatingFileSinkBuilder {
+            path: path.as_ref().to_path_buf(),
+            rotation: Rotation::Never,
+            retention: None,
+            compress: false,
+            colors: false,
+        }
+    }
+
+    // DEBUG_FILE=/var/tmp/app.log with DEBUG_FILE_ROTATE, DEBUG_FILE_KEEP and
+    // DEBUG_FILE_GZIP. A file that can't be opened is reported once on stderr.
+    pub(crate) fn from_env() -> Option<RotatingFileSink> {
+        let builder = builder_from(|name| env::var(name).ok())?;
+        let path = builder.path.clone();
+
+        match builder.build() {
+            Ok(sink) => Some(sink),
+            Err(error) => {
+                eprintln!("dbug: can't open DEBUG_FILE {}: {}", path.display(), error);
+                None
+            }
+        }
+    }
+
+    fn write_at(&self, line: &str, now: SystemTime) {
+        let Ok(mut active) = self.active.lock() else {
+            return;
+        };
+
+        let line = line.replace('\r', "\\r").replace('\n', "\\n");
+        let len = line.len() as u64 + 1;
+        let period = self
+            .rotation
+            .period()
+            .map_or(0, |period| secs(now) / period);
+        let due = match self.rotation {
+            Rotation::Never => false,
+            Rotation::Size(max) => active.size + len > max,
+            Rotation::Hourly | Rotation::Daily => period != active.period,
+        };
+        // An empty file is never worth rotating, it just moves to the new period
+        if due && active.size > 0 {
+            self.rotate(&mut active, now);
+        }
+        active.period = period;
+
+        if let Some(file) = &mut active.file
+            && writeln!(file, "{}", line).is_ok()
+        {
+            active.size += len;
+        }
+    }
+
+    fn rotate(&self, active: &mut Active, now: SystemTime) {
+        let stamp = match self.rotation.period() {
+            Some(period) => {
+                let start = UNIX_EPOCH + Duration::from_secs(active.period * period);
+                let stamp = timestamp::render(start, Timestamp::Utc, TimePrecision::Millis);
+                stamp[..if period == 3600 { 13 } else { 10 }].to_string()
+            }
+            None => timestamp::render(now, Timestamp::Utc, TimePrecision::Millis)
+                .trim_end_matches('Z')
+                .replace(':', "-"),
+        };
+
+        let name = self.file_name();
+        let mut target = self.path.with_file_name(format!("{}.{}", name, stamp));
+        let mut n = 0;
+        while target.exists() || gz_path(&target).exists() {
+            n += 1;
+            target = self
+                .path
+                .with_file_name(format!("{}.{}.{}", name, stamp, n));
+        }
+
+        // Windows can't rename a file that is still open
+        if let Some(mut file) = active.file.take() {
+            let _ = file.flush();
+        }
+        let rotated = fs::rename(&self.path, &target).is_ok();
+        match open(&self.path) {
+            Ok(file) => {
+                active.file = Some(file);
+                if rotated {
+                    active.size = 0;
+                }
+            }
+            Err(_) => return,
+        }
+
+        if rotated && self.compress {
+            compress(&target);
+        }
+        if let Some(retention) = self.retention {
+            self.prune(retention, now);
+        }
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn prune(&self, retention: Retention, now: SystemTime) {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+
+        let name = self.file_name();
+        let mut rotated: Vec<((String, u32), PathBuf, SystemTime)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let key = rotated_key(&name, &entry.file_name().to_string_lossy())?;
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((key, entry.path(), modified))
+            })
+            .collect();
+        // Newest first, the time stamps in the names sort chronologically
+        rotated.sort_by(|a, b| b.0.cmp(&a.0));
+
+        for (i, (_, path, modified)) in rotated.iter().enumerate() {
+            let expired = match retention {
+                Retention::Files(keep) => i >= keep,
+                Retention::Days(days) => now
+                    .duration_since(*modified)
+                    .is_ok_and(|age| age > Duration::from_secs(days.saturating_mul(86_400))),
+            };
+            if expired {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+impl Sink for RotatingFileSink {
+    fn write_line(&self, line: &str) {
+        self.write_at(line, SystemTime::now());
+    }
+
+    fn flush(&self) {
+        if let Ok(mut active) = self.active.lock()
+            && let Some(file) = &mut active.file
+        {
+            let _ = file.flush();
+        }
+    }
+
+    fn colors(&self) -> bool {
+        self.colors
+    }
+
+    fn single_line(&self) -> bool {
+        true
+    }
+}
+
+// The stamp and counter of a file `rotate` named `{name}.{stamp}[.{n}][.gz]`,
+// other files that start with `{name}.` aren't ours to delete
+fn rotated_key(name: &str, file_name: &str) -> Option<(String, u32)> {
+    // Daily, hourly and size rotation stamps are prefixes of this
+    const SHAPE: &str = "0000-00-00T00-00-00.000";
+
+    let rest = file_name.strip_prefix(name)?.strip_prefix('.')?;
+    let rest = rest.strip_suffix(".gz").unwrap_or(rest);
+
+    [10, 13, 23].into_iter().find_map(|len| {
+        let stamp = rest.get(..len)?;
+        let shaped = stamp
+            .bytes()
+            .zip(SHAPE.bytes())
+            .all(|(c, shape)| match shape {
+                b'0' => c.is_ascii_digit(),
+                shape => c == shape,
+            });
+        let n = match &rest[len..] {
+            "" => 0,
+            n => {
+                let n = n.strip_prefix('.')?;
+                if !n.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                n.parse().ok()?
+            }
+        };
+
+        shaped.then(|| (stamp.to_string(), n))
+    })
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+#[cfg(feature = "gzip")]
+fn compress(path: &Path) {
+    use flate2::{Compression, write::GzEncoder};
+
+    let gzip = || -> io::Result<()> {
+        let mut input = File::open(path)?;
+        let mut encoder = GzEncoder::new(File::create(gz_path(path))?, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?;
+
+        Ok(())
+    };
+
+    if gzip().is_ok() {
+        let _ = fs::remove_file(path);
+    } else {
+        let _ = fs::remove_file(gz_path(path));
+    }
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress(_path: &Path) {}
+
+// `10M`, `512k`, `1GB` or a number of bytes
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_ascii_lowercase();
+    let size = size.strip_suffix('b').unwrap_or(&size);
+    let (number, unit) = match size.char_indices().last()? {
+        (i, 'k') => (&size[..i], 1 << 10),
+        (i, 'm') => (&size[..i], 1 << 20),
+        (i, 'g') => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit))
+}
+
+fn parse_rotation(rotation: &str) -> Option<Rotation> {
+    match rotation.trim() {
+        "hourly" => Some(Rotation::Hourly),
+        "daily" => Some(Rotation::Daily),
+        "never" => Some(Rotation::Never),
+        size => parse_size(size).map(Rotation::Size),
+    }
+}
+
+// `5` keeps five files, `7d` a week of them
+fn parse_retention(retention: &str) -> Option<Retention> {
+    let retention = retention.trim();
+    match retention.strip_suffix('d') {
+        Some(days) => days
+            .parse::<u64>()
+            .ok()
+            .filter(|days| days.checked_mul(86_400).is_some())
+            .map(Retention::Days),
+        None => retention.parse().ok().map(Retention::Files),
+    }
+}
+
+fn builder_from(var: impl Fn(&str) -> Option<String>) -> Option<RotatingFileSinkBuilder> {
+    let path = var("DEBUG_FILE").filter(|path| !path.is_empty())?;
+
+    let mut builder = RotatingFileSink::builder(path);
+    if let Some(rotation) = var("DEBUG_FILE_ROTATE").as_deref().and_then(parse_rotation) {
+        builder = builder.rotation(rotation);
+    }
+    if let Some(retention) = var("DEBUG_FILE_KEEP").as_deref().and_then(parse_retention) {
+        builder = builder.retention(retention);
+    }
+    builder.compress = cfg!(feature = "gzip")
+        && matches!(var("DEBUG_FILE_GZIP").as_deref(), Some("1" | "true" | "on"));
+
+    Some(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory per test, so they can run in parallel
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dbug-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_the_newest_files() {
+        let dir = temp_dir("size");
+        let path = dir.join("app.log");
+        let sink = RotatingFileSink::builder(&path)
+            .rotation(Rotation::Size(10))
+            .retention(Retention::Files(2))
+            .build()
+            .unwrap();
+
+        let start = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        for (i, line) in ["one", "two\nlines", "three", "four", "five"]
+            .iter()
+            .enumerate()
+        {
+            sink.write_at(line, start + Duration::from_secs(i as u64));
+        }
+
+        assert_eq!(
+            files(&dir),
+            [
+                "app.log",
+                "app.log.2024-02-29T12-34-58.000",
+                "app.log.2024-02-29T12-34-59.000"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("app.log.2024-02-29T12-34-58.000")).unwrap(),
+            "two\\nlines\n"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "four\nfive\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn multi_line_messages_stay_on_one_line() {
+        let _guard = crate::filter::lock_for_test();
+        crate::enable("rotating:multi");
+
+        let dir = temp_dir("multi");
+        let path = dir.join("app.log");
+        let logger = crate::Logger::builder("rotating:multi")
+            .sink(RotatingFileSink::builder(&path).build().unwrap())
+            .build();
+        crate::dbug!(logger, "multi\nline"; k = 1);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+        assert!(contents.ends_with("Z rotating:multi multi\\nline k=1\n"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn retention_leaves_other_files_alone() {
+        let dir = temp_dir("foreign");
+        let path = dir.join("app.log");
+        for name in [
+            "app.log.bak",
+            "app.log.zzz-user-notes",
+            "app.log.2024-02-28",
+            "app.log.2024-02-29.notes",
+        ] {
+            fs::write(dir.join(name), "kept\n").unwrap();
+        }
+        let sink = RotatingFileSink::builder(&path)
+            .rotation(Rotation::Daily)
+            .retention(Retention::Files(1))
+            .build()
+            .unwrap();
+
+        let day = |d: u64| UNIX_EPOCH + Duration::from_secs(d * 86_400);
+        sink.write_at("one", day(19_782));
+        sink.write_at("two", day(19_783));
+
+        assert_eq!(
+            files(&dir),
+            [
+                "app.log",
+                "app.log.2024-02-29",
+                "app.log.2024-02-29.notes",
+                "app.log.bak",
+                "app.log.zzz-user-notes"
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn long_retention_keeps_every_file() {
+        let dir = temp_dir("days");
+        let path = dir.join("app.log");
+        let sink = RotatingFileSink::builder(&path)
+            .rotation(Rotation::Size(1))
+            .retention(Retention::Days(u64::MAX))
+            .build()
+            .unwrap();
+
+        // Days after the files were written, so their age is checked
+        let later = |d: u64| SystemTime::now() + Duration::from_secs(d * 86_400);
+        for d in 1..4 {
+            sink.write_at("line", later(d));
+        }
+
+        assert_eq!(files(&dir).len(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotated_names_are_recognized() {
+        assert_eq!(
+            rotated_key("app.log", "app.log.2024-02-29"),
+            Some(("2024-02-29".into(), 0))
+        );
+        assert_eq!(
+            rotated_key("app.log", "app.log.2024-02-29T13.2.gz"),
+            Some(("2024-02-29T13".into(), 2))
+        );
+        assert_eq!(
+            rotated_key("app.log", "app.log.2024-02-29T12-34-58.000"),
+            Some(("2024-02-29T12-34-58.000".into(), 0))
+        );
+        assert_eq!(rotated_key("app.log", "app.log.bak"), None);
+        assert_eq!(rotated_key("app.log", "app.log.2024-02-29.bak"), None);
+        assert_eq!(rotated_key("app.log", "other.log.2024-02-29"), None);
+    }
+
+    #[test]
+    fn rotates_at_the_start_of_each_period() {
+        let dir = temp_dir("daily");
+        let path = dir.join("app.log");
+        let sink = RotatingFileSink::builder(&path)
+            .rotation(Rotation::Daily)
+            .build()
+            .unwrap();
+
+        let day = |d: u64, h: u64| UNIX_EPOCH + Duration::from_secs(d * 86_400 + h * 3600);
+        sink.write_at("starts a new day", day(19_782, 1));
+        sink.write_at("same day", day(19_782, 23));
+        sink.write_at("next day", day(19_783, 0));
+
+        assert_eq!(files(&dir), ["app.log", "app.log.2024-02-29"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("app.log.2024-02-29")).unwrap(),
+            "starts a new day\nsame day\n"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "next day\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reads_settings_from_env() {
+        assert_eq!(parse_size("10M"), Some(10 << 20));
+        assert_eq!(parse_size("512kb"), Some(512 << 10));
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_size("999999999999g"), None);
+        assert_eq!(parse_rotation("hourly"), Some(Rotation::Hourly));
+        assert_eq!(parse_retention("7d"), Some(Retention::Days(7)));
+        assert_eq!(parse_retention("5"), Some(Retention::Files(5)));
+        assert_eq!(parse_retention("213503982334602d"), None);
+
+        let builder = builder_from(|name| match name {
+            "DEBUG_FILE" => Some("/var/tmp/app.log".into()),
+            "DEBUG_FILE_ROTATE" => Some("daily".into()),
+            "DEBUG_FILE_KEEP" => Some("3".into()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(builder.path, Path::new("/var/tmp/app.log"));
+        assert_eq!(builder.rotation, Rotation::Daily);
+        assert_eq!(builder.retention, Some(Retention::Files(3)));
+        assert!(builder_from(|_| None).is_none());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn compresses_rotated_files() {
+        let dir = temp_dir("gzip");
+        let path = dir.join("app.log");
+        let sink = RotatingFileSink::builder(&path)
+            .rotation(Rotation::Size(1))
+            .compress(true)
+            .build()
+            .unwrap();
+
+        sink.write_at("one", UNIX_EPOCH);
+        sink.write_at("two", UNIX_EPOCH);
+
+        assert_eq!(
+            files(&dir),
+            ["app.log", "app.log.1970-01-01T00-00-00.000.gz"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
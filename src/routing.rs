@@ -0,0 +1,195 @@
+use std::{
+    env,
+    sync::{Arc, RwLock},
+};
+
+use crate::{FileSink, Format, Sink, StderrSink, StdoutSink, filter::Filter};
+
+/// A sink a route writes to, with the format to render lines in.
+pub(crate) struct Target {
+    pub(crate) sink: Arc<dyn Sink>,
+    // `None` keeps the logger's own format
+    pub(crate) format: Option<Format>,
+}
+
+/// Namespaces, in `DEBUG` syntax, and the sinks their lines go to.
+pub struct Route {
+    filter: Filter,
+    targets: Vec<Target>,
+}
+
+impl Route {
+    /// A route for `namespaces`, such as `"db*"` or `"http*,-http:health"`.
+    pub fn new(namespaces: &str) -> Self {
+        Route {
+            filter: Filter::parse(namespaces),
+            targets: vec![],
+        }
+    }
+
+    /// Adds a sink, each line goes to every sink of its route.
+    pub fn sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.targets.push(Target {
+            sink: Arc::new(sink),
+            format: None,
+        });
+        self
+    }
+
+    /// Adds a sink that gets lines in `format`, whatever the logger's format is.
+    pub fn sink_with_format(mut self, sink: impl Sink + 'static, format: Format) -> Self {
+        self.targets.push(Target {
+            sink: Arc::new(sink),
+            format: Some(format),
+        });
+        self
+    }
+}
+
+/// Sends each namespace's lines to the sinks of the first route that matches
+/// it. Namespaces no route matches go to the default sink, and so do routes
+/// without any sinks.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    pub fn route(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    pub(crate) fn targets(&self, namespace: &str) -> Option<&[Target]> {
+        self.routes
+            .iter()
+            .find(|route| !route.targets.is_empty() && route.filter.enabled(namespace))
+            .map(|route| route.targets.as_slice())
+    }
+
+    // DEBUG_ROUTES="db* -> db.log; http* -> json@stderr, stdout; * -> stdout"
+    fn from_env() -> Router {
+        parse_routes(&env::var("DEBUG_ROUTES").unwrap_or_default())
+    }
+}
+
+// `stdout`, `stderr` or a file path, optionally after `json@` or `human@`
+fn parse_target(target: &str) -> Option<Target> {
+    let (format, destination) = match target.split_once('@') {
+        Some(("json", destination)) => (Some(Format::Json), destination),
+        Some(("human", destination)) => (Some(Format::Human), destination),
+        _ => (None, target),
+    };
+
+    let sink: Arc<dyn Sink> = match destination.trim() {
+        "" => return None,
+        "stdout" => Arc::new(StdoutSink),
+        "stderr" => Arc::new(StderrSink),
+        path => match FileSink::new(path) {
+            Ok(sink) => Arc::new(sink),
+            Err(error) => {
+                eprintln!("dbug: can't open route file {}: {}", path, error);
+                return None;
+            }
+        },
+    };
+
+    Some(Target { sink, format })
+}
+
+fn parse_routes(routes: &str) -> Router {
+    let mut router = Router::new();
+    for rule in routes.split(';') {
+        let Some((namespaces, targets)) = rule.split_once("->") else {
+            continue;
+        };
+
+        let mut route = Route::new(namespaces);
+        route.targets = targets
+            .split(',')
+            .map(str::trim)
+            .filter_map(parse_target)
+            .collect();
+        // Without a sink to write to, the namespaces keep the default one
+        if !route.targets.is_empty() {
+            router = router.route(route);
+        }
+    }
+
+    router
+}
+
+// None until the first line looks for a route, which reads DEBUG_ROUTES
+static ROUTER: RwLock<Option<Arc<Router>>> = RwLock::new(None);
+
+/// Replaces the routes, including any read from `DEBUG_ROUTES`. Loggers built
+/// with their own sink keep writing to it.
+pub fn set_router(router: Router) {
+    let mut current = ROUTER.write().unwrap_or_else(|e| e.into_inner());
+    *current = Some(Arc::new(router));
+}
+
+pub(crate) fn router() -> Arc<Router> {
+    if let Ok(router) = ROUTER.read()
+        && let Some(router) = router.as_ref()
+    {
+        return router.clone();
+    }
+
+    let mut router = ROUTER.write().unwrap_or_else(|e| e.into_inner());
+    router
+        .get_or_insert_with(|| Arc::new(Router::from_env()))
+        .clone()
+}
+
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub(crate) fn flush() {
+    for route in &router().routes {
+        for target in &route.targets {
+            target.sink.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemorySink;
+
+    #[test]
+    fn first_matching_route_wins() {
+        let router = Router::new()
+            .route(
+                Route::new("db*,-db:pool")
+                    .sink(MemorySink::new())
+                    .sink_with_format(MemorySink::new(), Format::Json),
+            )
+            .route(Route::new("db:*").sink(MemorySink::new()))
+            .route(Route::new("http*"));
+
+        assert_eq!(router.targets("db:read").map(<[_]>::len), Some(2));
+        assert_eq!(router.targets("db:pool").map(<[_]>::len), Some(1));
+        assert!(router.targets("http:server").is_none());
+        assert!(router.targets("app").is_none());
+    }
+
+    #[test]
+    fn parses_routes_from_env() {
+        let router = parse_routes(
+            "db* -> json@stderr, stdout; http*,-http:health -> stderr; broken; \
+             http:health -> /nonexistent/health.log",
+        );
+
+        let db = router.targets("db:read").unwrap();
+        assert_eq!(db.len(), 2);
+        assert_eq!(db[0].format, Some(Format::Json));
+        assert_eq!(db[1].format, None);
+        assert_eq!(router.targets("http:server").map(<[_]>::len), Some(1));
+        assert!(router.targets("http:health").is_none());
+        assert_eq!(router.routes.len(), 2);
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, IsTerminal, Write},
+    path::Path,
+    sync::{Arc, Mutex, OnceLock, RwLock},
+};
+
+use crate::{ColorDepth, RotatingFileSink};
+
+pub trait Sink: Send + Sync {
+    fn write_line(&self, line: &str);
+
+    fn flush(&self) {}
+
+    /// Whether lines for this sink should contain ANSI colors.
+    fn colors(&self) -> bool {
+        false
+    }
+
+    /// Which escapes to use when `colors` is on.
+    fn color_depth(&self) -> ColorDepth {
+        ColorDepth::detect()
+    }
+
+    /// Whether every record has to fit on one line, with line breaks in
+    /// messages written as `\n`.
+    fn single_line(&self) -> bool {
+        false
+    }
+}
+
+// Node's DEBUG_COLORS accepts yes/on/true/enabled, no/off/false/disabled or a
+// number. Anything else goes through `Number()` to NaN, which turns colors off.
+fn parse_debug_colors(value: &str) -> bool {
+    let value = value.trim().to_lowercase();
+
+    match value.as_str() {
+        "yes" | "on" | "true" | "enabled" => true,
+        "no" | "off" | "false" | "disabled" => false,
+        _ => value
+            .parse::<f64>()
+            .is_ok_and(|number| number != 0.0 && !number.is_nan()),
+    }
+}
+
+fn colors_from(var: impl Fn(&str) -> Option<String>, is_terminal: bool) -> bool {
+    if let Some(colors) = var("DEBUG_COLORS") {
+        return parse_debug_colors(&colors);
+    }
+
+    if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+
+    if let Some(force) = var("FORCE_COLOR") {
+        return !matches!(force.as_str(), "0" | "false");
+    }
+
+    if var("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+        return true;
+    }
+
+    if var("CLICOLOR").is_some_and(|value| value == "0") {
+        return false;
+    }
+
+    is_terminal
+}
+
+/// Decides whether output going somewhere should be colored, from `DEBUG_COLORS`,
+/// `NO_COLOR`, `FORCE_COLOR`, `CLICOLOR`/`CLICOLOR_FORCE` and, when none of those
+/// settle it, whether it is a terminal.
+pub fn colors_enabled(is_terminal: bool) -> bool {
+    colors_from(|name| env::var(name).ok(), is_terminal)
+}
+
+pub struct StdoutSink;
+
+impl Sink for StdoutSink {
+    fn write_line(&self, line: &str) {
+        let _ = writeln!(io::stdout().lock(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+    }
+
+    fn colors(&self) -> bool {
+        static COLORS: OnceLock<bool> = OnceLock::new();
+
+        *COLORS.get_or_init(|| colors_enabled(io::stdout().is_terminal()))
+    }
+}
+
+pub struct StderrSink;
+
+impl Sink for StderrSink {
+    fn write_line(&self, line: &str) {
+        let _ = writeln!(io::stderr().lock(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+
+    fn colors(&self) -> bool {
+        static COLORS: OnceLock<bool> = OnceLock::new();
+
+        *COLORS.get_or_init(|| colors_enabled(io::stderr().is_terminal()))
+    }
+}
+
+pub struct FileSink {
+    file: Mutex<File>,
+    colors: bool,
+}
+
+impl FileSink {
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let colors = colors_enabled(file.is_terminal());
+
+        Ok(FileSink {
+            file: Mutex::new(file),
+            colors,
+        })
+    }
+}
+
+impl Sink for FileSink {
+    fn write_line(&self, line: &str) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+
+    fn colors(&self) -> bool {
+        self.colors
+    }
+}
+
+/// Collects lines in memory, clones share the same buffer.
+/// Colors are off unless asked for with `with_colors`.
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    lines: Arc<Mutex<Vec<String>>>,
+    colors: bool,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        MemorySink::default()
+    }
+
+    pub fn with_colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines
+            .lock()
+            .map(|lines| lines.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut lines) = self.lines.lock() {
+            lines.clear();
+        }
+    }
+}
+
+impl Sink for MemorySink {
+    fn write_line(&self, line: &str) {
+        if let Ok(mut lines) = self.lines.lock() {
+            lines.push(line.to_string());
+        }
+    }
+
+    fn colors(&self) -> bool {
+        self.colors
+    }
+}
+
+static DEFAULT_SINK: RwLock<Option<Arc<dyn Sink>>> = RwLock::new(None);
+
+/// Sets the sink used by every `Logger` that wasn't built with its own sink.
+pub fn set_default_sink(sink: impl Sink + 'static) {
+    if let Ok(mut default) = DEFAULT_SINK.write() {
+        *default = Some(Arc::new(sink));
+    }
+}
+
+/// The sink set with `set_default_sink`, otherwise a `RotatingFileSink` when
+/// `DEBUG_FILE` is set, otherwise stdout.
+pub fn default_sink() -> Arc<dyn Sink> {
+    static ENV_SINK: OnceLock<Arc<dyn Sink>> = OnceLock::new();
+
+    DEFAULT_SINK
+        .read()
+        .ok()
+        .and_then(|default| default.clone())
+        .unwrap_or_else(|| {
+            ENV_SINK
+                .get_or_init(|| match RotatingFileSink::from_env() {
+                    Some(sink) => Arc::new(sink),
+                    None => Arc::new(StdoutSink),
+                })
+                .clone()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_sink_clones_share_lines() {
+        let sink = MemorySink::new();
+        let clone = sink.clone();
+
+        clone.write_line("one");
+        clone.write_line("two");
+
+        assert_eq!(sink.lines(), vec!["one", "two"]);
+
+        sink.clear();
+        assert!(clone.lines().is_empty());
+    }
+
+    #[test]
+    fn color_env_vars_take_precedence_over_terminal_detection() {
+        let colors = |vars: &[(&str, &str)], is_terminal| {
+            colors_from(
+                |name| {
+                    vars.iter()
+                        .find(|(key, _)| *key == name)
+                        .map(|(_, value)| value.to_string())
+                },
+                is_terminal,
+            )
+        };
+
+        assert!(colors(&[], true));
+        assert!(!colors(&[], false));
+        assert!(!colors(&[("NO_COLOR", "1")], true));
+        assert!(colors(&[("NO_COLOR", "")], true));
+        assert!(colors(&[("FORCE_COLOR", "1")], false));
+        assert!(!colors(&[("FORCE_COLOR", "0")], true));
+        assert!(!colors(&[("CLICOLOR", "0")], true));
+        assert!(colors(&[("CLICOLOR_FORCE", "1")], false));
+        assert!(colors(&[("DEBUG_COLORS", "yes"), ("NO_COLOR", "1")], false));
+        assert!(!colors(
+            &[("DEBUG_COLORS", "0"), ("FORCE_COLOR", "1")],
+            true
+        ));
+        assert!(!colors(&[("DEBUG_COLORS", "maybe")], true));
+        assert!(!colors(&[("DEBUG_COLORS", "NaN")], true));
+        assert!(colors(&[("DEBUG_COLORS", "2")], false));
+    }
+}
@@ -0,0 +1,244 @@
+use std::{fmt, fmt::Write, sync::Arc};
+
+use crate::{ColorDepth, Record, color::colorize, duration::humanize};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Literal(String),
+    Namespace,
+    Message,
+    Fields,
+    Diff,
+    Time,
+    Uptime,
+    Thread,
+    Pid,
+    File,
+    Line,
+    Module,
+    Location,
+    Level,
+}
+
+impl Piece {
+    fn placeholder(name: &str) -> Option<Piece> {
+        let piece = match name {
+            "ns" => Piece::Namespace,
+            "msg" => Piece::Message,
+            "fields" => Piece::Fields,
+            "diff" => Piece::Diff,
+            "time" => Piece::Time,
+            "uptime" => Piece::Uptime,
+            "thread" => Piece::Thread,
+            "pid" => Piece::Pid,
+            "file" => Piece::File,
+            "line" => Piece::Line,
+            "module" => Piece::Module,
+            "location" => Piece::Location,
+            "level" => Piece::Level,
+            _ => return None,
+        };
+
+        Some(piece)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TemplateError {
+    UnknownPlaceholder(String),
+    Unclosed,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder(name) => {
+                write!(f, "unknown placeholder {{{}}}", name)
+            }
+            TemplateError::Unclosed => write!(f, "unclosed {{ in template"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// A line layout such as `"{time} {ns} {level} {msg} {fields} {diff}"`, parsed once.
+///
+/// Placeholders are `{ns}`, `{msg}`, `{fields}`, `{diff}`, `{time}`, `{uptime}`,
+/// `{thread}`, `{pid}`, `{file}`, `{line}`, `{module}`, `{location}` and
+/// `{level}`. Use `{{` and `}}` for literal braces.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pieces: Arc<[Piece]>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, TemplateError> {
+        let mut pieces = vec![];
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(TemplateError::Unclosed),
+                        }
+                    }
+
+                    let piece =
+                        Piece::placeholder(&name).ok_or(TemplateError::UnknownPlaceholder(name))?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(piece);
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+
+        Ok(Template {
+            pieces: pieces.into(),
+        })
+    }
+
+    pub(crate) fn render(&self, record: &Record, colors: Option<ColorDepth>) -> String {
+        let mut line = String::with_capacity(64 + record.message.len());
+
+        for piece in self.pieces.iter() {
+            let _ = match piece {
+                Piece::Literal(literal) => write!(line, "{}", literal),
+                Piece::Namespace => match colors {
+                    Some(depth) => {
+                        write!(line, "{}", colorize(record.color, depth, record.namespace))
+                    }
+                    None => write!(line, "{}", record.namespace),
+                },
+                Piece::Message => write!(line, "{}", record.message()),
+                Piece::Fields => {
+                    for (i, field) in record.fields.iter().enumerate() {
+                        if i > 0 {
+                            line.push(' ');
+                        }
+                        let _ = write!(line, "{}", field);
+                    }
+                    Ok(())
+                }
+                Piece::Diff => {
+                    let diff = match record.elapsed {
+                        Some(elapsed) => humanize(elapsed, record.precision),
+                        None => "+0ms".to_string(),
+                    };
+                    match colors {
+                        Some(depth) => write!(line, "{}", colorize(record.color, depth, &diff)),
+                        None => write!(line, "{}", diff),
+                    }
+                }
+                Piece::Time => write!(line, "{}", record.time()),
+                Piece::Uptime => write!(line, "{}", record.uptime()),
+                Piece::Thread => write!(line, "{}", record.thread),
+                Piece::Pid => write!(line, "{}", record.pid),
+                Piece::File => match record.location {
+                    Some(location) => write!(line, "{}", location.file),
+                    None => Ok(()),
+                },
+                Piece::Line => match record.location {
+                    Some(location) => write!(line, "{}", location.line),
+                    None => Ok(()),
+                },
+                Piece::Module => match record.location.and_then(|location| location.module) {
+                    Some(module) => write!(line, "{}", module),
+                    None => Ok(()),
+                },
+                // `file:line`, linked to the file with `LocationStyle::Hyperlink`
+                Piece::Location => match record.location {
+                    Some(location) => {
+                        write!(line, "{}", location.render(record.location_style, colors))
+                    }
+                    None => Ok(()),
+                },
+                Piece::Level => match record.level {
+                    Some(level) => write!(line, "{}", level),
+                    None => Ok(()),
+                },
+            };
+        }
+
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Field, Level, Location, LocationStyle, color::Rgb};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn renders_every_placeholder() {
+        let template = Template::parse(
+            "{time} [{level}] {{{ns}}} {msg} {fields} {diff} {thread}/{pid} {file}:{line} {module} {uptime}",
+        )
+        .unwrap();
+        let fields = [Field::new("a", 1), Field::new("b", "x")];
+        let mut record = Record::new("db", Some(Level::Warn), "slow", &fields);
+        record.elapsed = Some(Duration::from_millis(12));
+        record.timestamp = UNIX_EPOCH;
+        record.thread = "main".into();
+        record.pid = 7;
+        record.location = Some(Location {
+            file: "src/db.rs",
+            line: 42,
+            module: Some("app::db"),
+        });
+        record.color = Rgb::from_hex("#0000CC").unwrap();
+        record.location_style = LocationStyle::Hyperlink;
+        record.uptime = Duration::from_millis(1_500);
+
+        assert_eq!(
+            template.render(&record, None),
+            "1970-01-01T00:00:00.000Z [WARN] {db} slow a=1 b=x +12ms main/7 src/db.rs:42 app::db 1.500s"
+        );
+        assert_eq!(
+            Template::parse("{ns} {diff}")
+                .unwrap()
+                .render(&record, Some(ColorDepth::Ansi256)),
+            "\x1b[1;38;5;20mdb\x1b[0m \x1b[1;38;5;20m+12ms\x1b[0m"
+        );
+        assert!(
+            Template::parse("{location}")
+                .unwrap()
+                .render(&record, Some(ColorDepth::Ansi256))
+                .starts_with("\x1b]8;;file://")
+        );
+        assert_eq!(
+            Template::parse("{location}").unwrap().render(&record, None),
+            "src/db.rs:42"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_unclosed_placeholders() {
+        assert_eq!(
+            Template::parse("{ns} {nope}"),
+            Err(TemplateError::UnknownPlaceholder("nope".into()))
+        );
+        assert_eq!(Template::parse("{ns"), Err(TemplateError::Unclosed));
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    env,
+    sync::OnceLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Whether lines start with the wall-clock time they were logged at.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Timestamp {
+    #[default]
+    Off,
+    /// RFC 3339 in UTC, `2026-10-17T09:15:02.042Z`
+    Utc,
+    /// RFC 3339 with the local offset, `2026-10-17T11:15:02.042+02:00`.
+    /// Falls back to UTC where the offset isn't known, which is off Unix.
+    Local,
+}
+
+impl Timestamp {
+    // DEBUG_TIMESTAMP=utc|local sets it for loggers without an explicit one
+    pub(crate) fn from_env() -> Timestamp {
+        static TIMESTAMP: OnceLock<Timestamp> = OnceLock::new();
+
+        *TIMESTAMP.get_or_init(|| match env::var("DEBUG_TIMESTAMP").as_deref() {
+            Ok("local") => Timestamp::Local,
+            Ok("utc" | "1" | "true" | "on") => Timestamp::Utc,
+            _ => Timestamp::Off,
+        })
+    }
+}
+
+/// Fractional digits of timestamps and the time since process start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimePrecision {
+    #[default]
+    Millis,
+    Micros,
+}
+
+impl TimePrecision {
+    // DEBUG_TIME_PRECISION=ms|us
+    pub(crate) fn from_env() -> TimePrecision {
+        static PRECISION: OnceLock<TimePrecision> = OnceLock::new();
+
+        *PRECISION.get_or_init(|| match env::var("DEBUG_TIME_PRECISION").as_deref() {
+            Ok("us") => TimePrecision::Micros,
+            _ => TimePrecision::Millis,
+        })
+    }
+}
+
+// DEBUG_UPTIME=1 adds the time since process start to loggers without an
+// explicit setting
+pub(crate) fn uptime_from_env() -> bool {
+    static UPTIME: OnceLock<bool> = OnceLock::new();
+
+    *UPTIME.get_or_init(|| matches!(env::var("DEBUG_UPTIME").as_deref(), Ok("1" | "true" | "on")))
+}
+
+// Days since 1970-01-01 to a (year, month, day) civil date,
+// from Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Formats `time` the way `timestamp` asks for, UTC when it is `Off`. The
+/// default, UTC with milliseconds, matches JavaScript's `toISOString()`.
+pub(crate) fn render(time: SystemTime, timestamp: Timestamp, precision: TimePrecision) -> String {
+    let offset = match timestamp {
+        Timestamp::Local => Some(local_offset(time)),
+        Timestamp::Utc | Timestamp::Off => None,
+    };
+
+    format_rfc3339(time, offset, precision)
+}
+
+// `offset` is in seconds east of UTC, `None` writes `Z`
+fn format_rfc3339(time: SystemTime, offset: Option<i64>, precision: TimePrecision) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64 + offset.unwrap_or(0);
+
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let secs_of_day = secs.rem_euclid(86_400);
+
+    let mut out = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+    );
+    match precision {
+        TimePrecision::Millis => out.push_str(&format!(".{:03}", since_epoch.subsec_millis())),
+        TimePrecision::Micros => out.push_str(&format!(".{:06}", since_epoch.subsec_micros())),
+    }
+    match offset {
+        Some(offset) => out.push_str(&format!(
+            "{}{:02}:{:02}",
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 3600,
+            offset.abs() % 3600 / 60
+        )),
+        None => out.push('Z'),
+    }
+
+    out
+}
+
+#[cfg(unix)]
+fn local_offset(time: SystemTime) -> i64 {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let time = secs as libc::time_t;
+
+    // SAFETY: `tm` is plain old data that `localtime_r` fills in, and unlike
+    // `localtime` it doesn't share a static buffer between threads
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return 0;
+        }
+
+        tm.tm_gmtoff as i64
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_time: SystemTime) -> i64 {
+    0
+}
+
+// How long ago the OS started this process. `starttime`, the 22nd field of
+// /proc/self/stat, is in clock ticks since boot, and /proc/uptime starts
+// with the seconds since boot.
+#[cfg(target_os = "linux")]
+pub(crate) fn process_age() -> Option<Duration> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    // SAFETY: `sysconf` only reads a configuration value
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+
+    process_age_from(&stat, &uptime, u64::try_from(ticks).ok()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn process_age() -> Option<Duration> {
+    None
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn process_age_from(stat: &str, uptime: &str, ticks_per_sec: u64) -> Option<Duration> {
+    // The command name in parentheses can contain spaces, the fields after it can't
+    let (_, fields) = stat.rsplit_once(')')?;
+    let start_ticks: u64 = fields.split_whitespace().nth(19)?.parse().ok()?;
+    let since_boot: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    if ticks_per_sec == 0 || !since_boot.is_finite() {
+        return None;
+    }
+
+    let started = Duration::from_secs(start_ticks / ticks_per_sec)
+        + Duration::from_secs(start_ticks % ticks_per_sec) / ticks_per_sec as u32;
+    Some(Duration::from_secs_f64(since_boot.max(0.0)).saturating_sub(started))
+}
+
+/// The time since process start as seconds, `12.345s`.
+pub(crate) fn uptime(elapsed: Duration, precision: TimePrecision) -> String {
+    match precision {
+        TimePrecision::Millis => format!("{}.{:03}s", elapsed.as_secs(), elapsed.subsec_millis()),
+        TimePrecision::Micros => format!("{}.{:06}s", elapsed.as_secs(), elapsed.subsec_micros()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn process_age_comes_from_proc() {
+        let stat = "4242 (my app) S 1 4242 4242 0 -1 4194560 100 0 0 0 1 0 0 0 20 0 1 0 \
+                    12345 1000 200";
+        assert_eq!(
+            process_age_from(stat, "150.50 300.00\n", 100),
+            Some(Duration::from_millis(27_050))
+        );
+        assert_eq!(
+            process_age_from(stat, "100.00 300.00\n", 100),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(process_age_from("garbage", "150.50", 100), None);
+    }
+
+    #[test]
+    fn formats_like_to_iso_string() {
+        let rfc3339 = |time| render(time, Timestamp::Off, TimePrecision::Millis);
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(rfc3339(time), "2024-02-29T12:34:56.789Z");
+    }
+
+    #[test]
+    fn formats_offsets_and_microseconds() {
+        let time = UNIX_EPOCH + Duration::from_micros(1_709_210_096_789_012);
+
+        assert_eq!(
+            format_rfc3339(time, Some(2 * 3600), TimePrecision::Micros),
+            "2024-02-29T14:34:56.789012+02:00"
+        );
+        assert_eq!(
+            format_rfc3339(time, Some(-(13 * 3600 + 30 * 60)), TimePrecision::Millis),
+            "2024-02-28T23:04:56.789-13:30"
+        );
+        assert_eq!(
+            uptime(Duration::from_micros(12_345_678), TimePrecision::Millis),
+            "12.345s"
+        );
+        assert_eq!(
+            uptime(Duration::from_micros(12_345_678), TimePrecision::Micros),
+            "12.345678s"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::{fmt, sync::Arc};
+
+use tracing_core::{
+    Event, Subscriber,
+    field::{Field as TracingField, Visit},
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan};
+
+use crate::{Field, Level, Location, Sink, Value, filter, loggers::Loggers};
+
+fn level(level: tracing_core::Level) -> Level {
+    match level {
+        tracing_core::Level::ERROR => Level::Error,
+        tracing_core::Level::WARN => Level::Warn,
+        tracing_core::Level::INFO => Level::Info,
+        tracing_core::Level::DEBUG => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    fields: Vec<Field>,
+}
+
+impl Fields {
+    fn push(&mut self, field: &TracingField, value: Value) {
+        self.fields.push(Field {
+            key: field.name(),
+            value,
+        });
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &TracingField, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.push(field, Value::debug(&value));
+        }
+    }
+
+    fn record_str(&mut self, field: &TracingField, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.push(field, value.into());
+        }
+    }
+
+    fn record_i64(&mut self, field: &TracingField, value: i64) {
+        self.push(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &TracingField, value: u64) {
+        self.push(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &TracingField, value: f64) {
+        self.push(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &TracingField, value: bool) {
+        self.push(field, value.into());
+    }
+}
+
+/// A `tracing_subscriber` layer that prints events the way `Logger::log` does.
+///
+/// An event's namespace is its target with `::` replaced by `:`, followed by the
+/// names of the spans it happened in, so an event in the `request` span of
+/// `myapp::http` is logged as `myapp:http:request` and filtered by `DEBUG` as such.
+pub struct DbugLayer {
+    loggers: Loggers,
+}
+
+impl DbugLayer {
+    pub fn new() -> Self {
+        DbugLayer {
+            loggers: Loggers::new(None),
+        }
+    }
+
+    pub fn with_sink(sink: impl Sink + 'static) -> Self {
+        DbugLayer {
+            loggers: Loggers::new(Some(Arc::new(sink))),
+        }
+    }
+}
+
+impl Default for DbugLayer {
+    fn default() -> Self {
+        DbugLayer::new()
+    }
+}
+
+impl<S> tracing_subscriber::Layer<S> for DbugLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+
+        let mut namespace = metadata.target().replace("::", ":");
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                namespace.push(':');
+                namespace.push_str(span.name());
+            }
+        }
+
+        // Checked before looking up the logger, so disabled namespaces never
+        // get one. This can't be a `Layer::enabled` check: that would hide the
+        // event from every other layer too, and spans aren't known there yet.
+        let level = level(*metadata.level());
+        if filter::threshold(&namespace).is_none_or(|threshold| level < threshold) {
+            return;
+        }
+        let logger = self.loggers.get(&namespace);
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let location = metadata
+            .file()
+            .zip(metadata.line())
+            .map(|(file, line)| Location {
+                file,
+                line,
+                module: metadata.module_path(),
+            });
+
+        logger.log_from(
+            Some(level),
+            format_args!("{}", fields.message),
+            &fields.fields,
+            location,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemorySink, enable};
+    use tracing::Dispatch;
+    use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+    #[test]
+    fn events_are_namespaced_by_target_and_spans() {
+        let _guard = filter::lock_for_test();
+        enable("dbug:tracing_layer:tests:request*=info");
+
+        let sink = MemorySink::new();
+        let subscriber = Registry::default().with(DbugLayer::with_sink(sink.clone()));
+        let dispatch = Dispatch::new(subscriber);
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::warn!("outside any span");
+
+            let request = tracing::info_span!("request");
+            let _request = request.enter();
+            tracing::warn!(status = 200, path = "/users", "request {}", "done");
+            tracing::debug!("below the threshold");
+
+            let db = tracing::info_span!("db");
+            let _db = db.enter();
+            tracing::info!(rows = 3u64, "query");
+        });
+
+        let lines = sink.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(
+            " dbug:tracing_layer:tests:request WARN  request done status=200 path=/users"
+        ));
+        assert!(lines[1].ends_with(" dbug:tracing_layer:tests:request:db INFO  query rows=3"));
+        // Only the enabled namespaces got a logger
+        let layer = dispatch.downcast_ref::<DbugLayer>().unwrap();
+        assert_eq!(layer.loggers.len(), 2);
+    }
+}